@@ -0,0 +1,119 @@
+//! Apply arbitrary AddList and DeleteList buffers to an archive, as PackFiles() and DeleteFiles() would
+//!
+//! The archive has to re-parse after every successful operation, unless emptied, whereupon the next one starts from a new one.
+//! It's kept in memory throughout, with only the files packed into it on disk.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate totalcmd_hrx;
+extern crate hrx;
+
+use totalcmd_hrx::{ProgressCallbacks, ArchiveState, BinaryPolicy, Config, delete_from_archive, pack_into_archive};
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use std::path::{Component, PathBuf, Path};
+use totalcmd_hrx::util::CListIter;
+use std::time::SystemTime;
+use hrx::HrxArchive;
+use std::sync::Once;
+use std::{env, fs};
+use std::process;
+
+
+static ARCHIVE: &str = "<===> a.txt\nA\n<===>\ncomment\n<===> dir/\n<===> dir/b.txt\nB\n";
+
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    base64: bool,
+    comment_sidecars: bool,
+    operations: Vec<Operation>,
+}
+
+#[derive(Debug, Arbitrary)]
+enum Operation {
+    Pack {
+        sub_path: Option<String>,
+        add_list: Vec<u8>,
+        save_paths: bool,
+    },
+    Delete { delete_list: Vec<u8> },
+}
+
+
+fuzz_target!(|input: Input| {
+    let source_dir = source_dir();
+    let config = Config {
+        binary_policy: if input.base64 {
+            BinaryPolicy::Base64
+        } else {
+            BinaryPolicy::Skip
+        },
+        comment_sidecars: input.comment_sidecars,
+        ..Config::default()
+    };
+
+    let mut archive = ARCHIVE.parse::<HrxArchive>().unwrap();
+    for operation in input.operations {
+        // Failed operations leave the file as it was
+        let mut modified = archive.clone();
+        let result = match operation {
+            Operation::Pack { sub_path, add_list, save_paths } => {
+                pack_into_archive(&mut modified,
+                                  sub_path,
+                                  &source_dir,
+                                  list(add_list).into_iter(),
+                                  save_paths,
+                                  &config,
+                                  &ProgressCallbacks::default())
+                    .map(|_| ())
+            }
+            Operation::Delete { delete_list } => {
+                delete_from_archive(&mut modified, "archive.hrx", list(delete_list).into_iter(), &config, &ProgressCallbacks::default())
+            }
+        };
+        if result.is_err() {
+            continue;
+        }
+
+        // Emptied archives are removed, and packing creates a new one
+        if modified.entries.is_empty() && modified.comment.is_none() {
+            archive = HrxArchive::new(config.boundary_length);
+            continue;
+        }
+
+        let mut serialised = vec![];
+        modified.serialise(&mut serialised).unwrap();
+        String::from_utf8(serialised.clone()).unwrap().parse::<HrxArchive>().unwrap();
+        ArchiveState::from_bytes(serialised, SystemTime::UNIX_EPOCH, &config).unwrap();
+        archive = modified;
+    }
+});
+
+
+/// Split `buf` as `CListIter` does the host's lists, leaving out the names the host would never pass, which point outside the
+/// source directory
+fn list(mut buf: Vec<u8>) -> Vec<String> {
+    buf.extend_from_slice(b"\0\0");
+    CListIter(buf.as_ptr())
+        .map(String::from_utf8_lossy)
+        .filter(|e| Path::new(&e[..]).components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)))
+        .map(|e| e.into_owned())
+        .collect()
+}
+
+/// The files to pack, only ever read, and so only written once
+fn source_dir() -> PathBuf {
+    static PREPARED: Once = Once::new();
+
+    let src = env::temp_dir().join("totalcmd-hrx-fuzz").join(process::id().to_string()).join("src");
+    PREPARED.call_once(|| {
+        fs::create_dir_all(src.join("dir")).unwrap();
+        fs::write(src.join("a.txt"), "Aaa\r\n").unwrap();
+        fs::write(src.join("a.txt.comment"), "A comment").unwrap();
+        fs::write(src.join("bin.dat"), b"\xFF\xFE\x00").unwrap();
+        fs::write(src.join("dir").join("b.txt"), "<===> b\n").unwrap();
+    });
+    src
+}
@@ -0,0 +1,33 @@
+//! Open arbitrary bytes as an archive, then list, decode and test every entry as the host would
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate totalcmd_hrx;
+
+use totalcmd_hrx::util::sanitise_entry_path;
+use totalcmd_hrx::{ArchiveState, Config, entry_mod_time};
+use std::time::SystemTime;
+
+
+fuzz_target!(|data: &[u8]| {
+    let (comment_sidecars, bytes) = match data.split_first() {
+        Some((&flags, bytes)) => ((flags & 1) != 0, bytes),
+        None => return,
+    };
+
+    let config = Config { comment_sidecars: comment_sidecars, ..Config::default() };
+    let mut state = match ArchiveState::from_bytes(bytes.to_vec(), SystemTime::UNIX_EPOCH, &config) {
+        Ok(state) => state,
+        Err(_) => return,
+    };
+
+    while let Some((_, entry, host_name)) = state.next_entry() {
+        let _ = entry_mod_time(entry);
+        let _ = sanitise_entry_path(host_name);
+
+        let _ = state.current_crc();
+        let _ = state.test_current_entry();
+    }
+});
@@ -0,0 +1,108 @@
+use std::num::NonZeroUsize;
+
+
+/// Plugin settings, as consumed by the packing and unpacking operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// The boundary length new archives start with, and the shortest one boundaries are shrunk to
+    pub boundary_length: NonZeroUsize,
+    /// Whether to shorten the boundaries of existing archives, if possible, when writing them
+    pub shrink_boundary: bool,
+    /// What to do with files that aren't valid UTF-8 when packing
+    pub binary_policy: BinaryPolicy,
+    /// Whether to keep the previous version of a modified archive as `*.hrx.bak`
+    pub backup: bool,
+    /// Whether to record the modification time of packed files in an `mtime: <RFC 3339>` entry comment
+    pub timestamps_in_comments: bool,
+    /// Whether to list the comment of each commented entry as a `<name>.comment` file, packed back into the comment
+    pub comment_sidecars: bool,
+    /// The order entries are written in
+    pub sort_order: SortOrder,
+    /// How line endings are converted when packing and extracting text files
+    pub line_endings: LineEndings,
+}
+
+/// How to pack files that aren't valid UTF-8, since HRX can only hold text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BinaryPolicy {
+    /// Leave the file out of the archive and report it back
+    #[default]
+    Skip,
+    /// Store the file base64-encoded and marked with an `encoding: base64` entry comment, decoded back when extracting
+    Base64,
+    /// Consider the file to be text in the specified encoding and store it converted to UTF-8
+    Transcode(LegacyEncoding),
+}
+
+/// The order the entries of modified archives are written in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// The order they were in, with new entries added at the end
+    #[default]
+    Keep,
+    /// By path, component-wise, so that the contents of each directory stay together
+    Path,
+}
+
+/// How the line endings of text files are converted, since HRX archives are usually expected to use LF
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEndings {
+    /// Pack and extract files as they are
+    #[default]
+    Keep,
+    /// Convert CRLF to LF when packing, recording the lines that had it in a `line-endings` entry comment to restore it when
+    /// extracting
+    Lf,
+    /// Like `Lf`, but also convert the LF line endings of entries without recorded ones to CRLF when extracting
+    Crlf,
+}
+
+/// Single-byte encodings files can be converted from under [`BinaryPolicy::Transcode`](enum.BinaryPolicy.html)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegacyEncoding {
+    /// ISO-8859-1, mapping each byte to the codepoint of the same value
+    Latin1,
+    /// Windows-1252, ISO-8859-1 with printable characters in the 0x80-0x9F range
+    Windows1252,
+}
+
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            boundary_length: NonZeroUsize::new(3).unwrap(),
+            shrink_boundary: false,
+            binary_policy: BinaryPolicy::default(),
+            backup: false,
+            timestamps_in_comments: false,
+            comment_sidecars: false,
+            sort_order: SortOrder::default(),
+            line_endings: LineEndings::default(),
+        }
+    }
+}
+
+impl LegacyEncoding {
+    /// Convert the specified text in this encoding to UTF-8
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use totalcmd_hrx::LegacyEncoding;
+    /// assert_eq!(LegacyEncoding::Latin1.decode(b"Gr\xFC\xDFe \x80"), "Grüße \u{80}");
+    /// assert_eq!(LegacyEncoding::Windows1252.decode(b"Gr\xFC\xDFe \x80"), "Grüße €");
+    /// ```
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        bytes.iter()
+            .map(|&b| match (self, b) {
+                (LegacyEncoding::Windows1252, 0x80..=0x9F) => WINDOWS_1252_80_9F[b as usize - 0x80],
+                _ => b as char,
+            })
+            .collect()
+    }
+}
+
+
+/// Undefined positions map to the C1 control of the same value, like in the WHATWG mapping
+static WINDOWS_1252_80_9F: [char; 0x20] = ['€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž', '\u{8F}',
+                                           '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}', 'ž', 'Ÿ'];
@@ -0,0 +1,217 @@
+//! The options dialog shown by [`ConfigurePacker`](../fn.ConfigurePacker.html), built from [`Settings::FIELDS`](../struct.Settings.html)
+//!
+//! There's no resource script, so the template is laid out in memory: a labelled control per setting, then OK and Cancel.
+
+
+use winapi::um::winuser::{DialogBoxIndirectParamW, SendDlgItemMessageW, IsDlgButtonChecked, GetWindowTextLengthW, SetWindowLongPtrW, GetWindowLongPtrW,
+                          SetDlgItemTextW, GetDlgItemTextW, CheckDlgButton, MessageBoxW, GetDlgItem, EndDialog, SetFocus, LPCDLGTEMPLATEW, BS_DEFPUSHBUTTON,
+                          BS_AUTOCHECKBOX, CBS_DROPDOWNLIST, DS_MODALFRAME, ES_AUTOHSCROLL, BS_PUSHBUTTON, WM_INITDIALOG, BST_UNCHECKED, GWLP_USERDATA,
+                          MB_ICONERROR, BST_CHECKED, CB_ADDSTRING, CB_GETCURSEL, CB_SETCURSEL, WS_VISIBLE, WS_TABSTOP, WS_CAPTION, WS_SYSMENU, WS_VSCROLL,
+                          WM_COMMAND, DS_SETFONT, WS_BORDER, DS_CENTER, ES_NUMBER, WS_CHILD, WS_POPUP, IDCANCEL, SS_LEFT, MB_OK, IDOK};
+use winapi::shared::minwindef::{HINSTANCE, LPARAM, WPARAM, LOWORD, FALSE, DWORD, UINT, TRUE};
+use winapi::shared::basetsd::INT_PTR;
+use winapi::shared::windef::HWND;
+use self::super::settings::{SettingKind, Settings};
+use self::super::guard::catch_panic;
+use libc::c_int;
+use std::iter;
+
+
+/// ID of the control for the first setting, the rest follow in order
+const FIRST_ID: c_int = 100;
+
+/// Predefined window class atoms, as the template refers to them
+const BUTTON: u16 = 0x0080;
+const EDIT: u16 = 0x0081;
+const STATIC: u16 = 0x0082;
+const COMBOBOX: u16 = 0x0085;
+
+/// Layout, in dialog units
+const MARGIN: i16 = 7;
+const LABEL_WIDTH: i16 = 90;
+const CONTROL_WIDTH: i16 = 150;
+const ROW_HEIGHT: i16 = 14;
+const ROW_SPACING: i16 = 18;
+const BUTTON_WIDTH: i16 = 50;
+
+
+/// Let the user edit `settings` in a modal dialog over `parent`
+///
+/// Returns whether they were accepted, in which case every edited value was valid and is set.
+pub fn edit_settings(parent: HWND, instance: HINSTANCE, settings: &mut Settings) -> bool {
+    let template = template();
+    unsafe { DialogBoxIndirectParamW(instance, template.as_ptr() as LPCDLGTEMPLATEW, parent, Some(dialog_proc), settings as *mut Settings as LPARAM) > 0 }
+}
+
+
+unsafe extern "system" fn dialog_proc(dialog: HWND, message: UINT, wparam: WPARAM, lparam: LPARAM) -> INT_PTR {
+    catch_panic(FALSE as INT_PTR, || match message {
+        WM_INITDIALOG => {
+            SetWindowLongPtrW(dialog, GWLP_USERDATA, lparam as _);
+            show(dialog, &*(lparam as *const Settings));
+            TRUE as INT_PTR
+        }
+        WM_COMMAND => {
+            match LOWORD(wparam as DWORD) as c_int {
+                IDOK => {
+                    let settings = &mut *(GetWindowLongPtrW(dialog, GWLP_USERDATA) as *mut Settings);
+                    if accept(dialog, settings) {
+                        EndDialog(dialog, 1);
+                    }
+                }
+                IDCANCEL => {
+                    EndDialog(dialog, 0);
+                }
+                _ => return FALSE as INT_PTR,
+            }
+            TRUE as INT_PTR
+        }
+        _ => FALSE as INT_PTR,
+    })
+}
+
+/// Fill the controls with the values of `settings`
+unsafe fn show(dialog: HWND, settings: &Settings) {
+    for (id, field) in (FIRST_ID..).zip(Settings::FIELDS.iter()) {
+        let value = settings.value(field.key).unwrap_or_default();
+        match field.kind {
+            SettingKind::Flag => {
+                CheckDlgButton(dialog, id, (if value == "1" { BST_CHECKED } else { BST_UNCHECKED }) as UINT);
+            }
+            SettingKind::Choice(choices) => {
+                for choice in choices {
+                    SendDlgItemMessageW(dialog, id, CB_ADDSTRING, 0, wide(choice).as_ptr() as LPARAM);
+                }
+                SendDlgItemMessageW(dialog, id, CB_SETCURSEL, choices.iter().position(|&c| c == value).unwrap_or(0), 0);
+            }
+            SettingKind::Number | SettingKind::Text => {
+                SetDlgItemTextW(dialog, id, wide(&value).as_ptr());
+            }
+        }
+    }
+}
+
+/// Set `settings` to the values in the controls, or point the user at the first invalid one and leave them be
+unsafe fn accept(dialog: HWND, settings: &mut Settings) -> bool {
+    let mut edited = settings.clone();
+    for (id, field) in (FIRST_ID..).zip(Settings::FIELDS.iter()) {
+        let value = match field.kind {
+            SettingKind::Flag => (if IsDlgButtonChecked(dialog, id) as WPARAM == BST_CHECKED { "1" } else { "0" }).to_string(),
+            SettingKind::Choice(choices) => choices.get(SendDlgItemMessageW(dialog, id, CB_GETCURSEL, 0, 0) as usize).cloned().unwrap_or_default().to_string(),
+            SettingKind::Number | SettingKind::Text => item_text(dialog, id),
+        };
+
+        if !edited.set_value(field.key, &value) {
+            let message = wide(&format!("\"{}\" isn't a valid {}.", value, field.label.to_lowercase()));
+            MessageBoxW(dialog, message.as_ptr(), wide("totalcmd-hrx").as_ptr(), MB_OK | MB_ICONERROR);
+            SetFocus(GetDlgItem(dialog, id));
+            return false;
+        }
+    }
+
+    *settings = edited;
+    true
+}
+
+unsafe fn item_text(dialog: HWND, id: c_int) -> String {
+    let mut text = vec![0u16; GetWindowTextLengthW(GetDlgItem(dialog, id)) as usize + 1];
+    let len = GetDlgItemTextW(dialog, id, text.as_mut_ptr(), text.len() as c_int);
+    String::from_utf16_lossy(&text[..len as usize])
+}
+
+
+/// A `DLGTEMPLATE` with its `DLGITEMTEMPLATE`s, DWORD-aligned as `DialogBoxIndirectParamW` requires
+fn template() -> Vec<u32> {
+    let rows = Settings::FIELDS.len() as i16;
+    let width = MARGIN + LABEL_WIDTH + CONTROL_WIDTH + MARGIN;
+    let height = MARGIN + rows * ROW_SPACING + ROW_HEIGHT + MARGIN;
+
+    let mut template = vec![];
+    push_dword(&mut template, DS_MODALFRAME | DS_SETFONT | DS_CENTER | WS_POPUP | WS_CAPTION | WS_SYSMENU);
+    push_dword(&mut template, 0);
+    // A label and a control per setting, but flags are checkboxes labelled by themselves, then the buttons
+    template.push((Settings::FIELDS.len() * 2 - Settings::FIELDS.iter().filter(|f| f.kind == SettingKind::Flag).count() + 2) as u16);
+    template.extend([0, 0, width, height].iter().map(|&c| c as u16));
+    // No menu, default class
+    template.extend(&[0, 0]);
+    template.extend(wide("HRX options"));
+    template.push(8);
+    template.extend(wide("MS Shell Dlg"));
+
+    for (i, (id, field)) in (FIRST_ID..).zip(Settings::FIELDS.iter()).enumerate() {
+        let y = MARGIN + i as i16 * ROW_SPACING;
+        let control_x = MARGIN + LABEL_WIDTH;
+        match field.kind {
+            SettingKind::Flag => {
+                // Labelled by itself, across both columns
+                push_item(&mut template,
+                          BS_AUTOCHECKBOX | WS_TABSTOP,
+                          (MARGIN, y, LABEL_WIDTH + CONTROL_WIDTH, ROW_HEIGHT),
+                          id,
+                          BUTTON,
+                          field.label);
+            }
+            SettingKind::Choice(_) => {
+                push_item(&mut template, SS_LEFT, (MARGIN, y + 2, LABEL_WIDTH, ROW_HEIGHT), -1, STATIC, field.label);
+                push_item(&mut template,
+                          CBS_DROPDOWNLIST | WS_VSCROLL | WS_TABSTOP,
+                          (control_x, y, CONTROL_WIDTH, ROW_HEIGHT * 6),
+                          id,
+                          COMBOBOX,
+                          "");
+            }
+            SettingKind::Number | SettingKind::Text => {
+                let number = if field.kind == SettingKind::Number { ES_NUMBER } else { 0 };
+                push_item(&mut template, SS_LEFT, (MARGIN, y + 2, LABEL_WIDTH, ROW_HEIGHT), -1, STATIC, field.label);
+                push_item(&mut template,
+                          ES_AUTOHSCROLL | WS_BORDER | WS_TABSTOP | number,
+                          (control_x, y, CONTROL_WIDTH, ROW_HEIGHT),
+                          id,
+                          EDIT,
+                          "");
+            }
+        }
+    }
+
+    let y = height - MARGIN - ROW_HEIGHT;
+    push_item(&mut template,
+              BS_DEFPUSHBUTTON | WS_TABSTOP,
+              (width - MARGIN - BUTTON_WIDTH * 2 - 4, y, BUTTON_WIDTH, ROW_HEIGHT),
+              IDOK,
+              BUTTON,
+              "OK");
+    push_item(&mut template,
+              BS_PUSHBUTTON | WS_TABSTOP,
+              (width - MARGIN - BUTTON_WIDTH, y, BUTTON_WIDTH, ROW_HEIGHT),
+              IDCANCEL,
+              BUTTON,
+              "Cancel");
+
+    if !template.len().is_multiple_of(2) {
+        template.push(0);
+    }
+    template.chunks(2).map(|c| c[0] as u32 | (c[1] as u32) << 16).collect()
+}
+
+fn push_item(template: &mut Vec<u16>, style: DWORD, (x, y, cx, cy): (i16, i16, i16, i16), id: c_int, class: u16, title: &str) {
+    if !template.len().is_multiple_of(2) {
+        template.push(0);
+    }
+
+    push_dword(template, WS_CHILD | WS_VISIBLE | style);
+    push_dword(template, 0);
+    template.extend([x, y, cx, cy].iter().map(|&c| c as u16));
+    template.push(id as u16);
+    template.extend(&[0xFFFF, class]);
+    template.extend(wide(title));
+    // No creation data
+    template.push(0);
+}
+
+fn push_dword(template: &mut Vec<u16>, dword: DWORD) {
+    template.extend(&[dword as u16, (dword >> 16) as u16]);
+}
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(iter::once(0)).collect()
+}
@@ -0,0 +1,122 @@
+use std::error::Error as StdError;
+use std::fmt;
+use libc::c_int;
+
+
+/// Some of the files weren't packed, as per the binary file policy, but the rest were
+pub const E_FILES_SKIPPED: c_int = 101;
+
+/// The entry's path would extract to outside of the destination
+pub const E_UNSAFE_PATH: c_int = 102;
+
+
+/// Everything that can go wrong with an archive operation
+///
+/// Each variant corresponds to one of the [WCX error codes](wcxhead/#error-codes), which the exports report it as, or, for
+/// what those have no code for, to one of the plugin's own, past `E_NOT_SUPPORTED`, which hosts show as unknown errors.
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::Error;
+/// assert_eq!(Error::BadData.code(), 12);
+/// assert_eq!(Error::Aborted.to_string(), "aborted by user");
+/// ```
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub enum Error {
+    /// No more files in archive
+    EndArchive,
+    /// Not enough memory
+    NoMemory,
+    /// CRC error in the data of the currently unpacked file
+    BadData,
+    /// The archive as a whole is bad, e.g. damaged headers
+    BadArchive,
+    /// Archive format unknown
+    UnknownFormat,
+    /// Cannot open existing file
+    Open,
+    /// Cannot create file
+    Create,
+    /// Error closing file
+    Close,
+    /// Error reading from file
+    Read,
+    /// Error writing to file
+    Write,
+    /// Buffer too small
+    SmallBuf,
+    /// Function aborted by user
+    Aborted,
+    /// No files found
+    NoFiles,
+    /// Too many files to pack
+    TooManyFiles,
+    /// Function not supported
+    NotSupported,
+    /// Some of the files weren't packed, as per the binary file policy, but the rest were
+    FilesSkipped,
+    /// The entry's path would extract to outside of the destination
+    UnsafePath,
+}
+
+impl Error {
+    /// Get the `E_*` code of this error
+    pub fn code(self) -> c_int {
+        match self {
+            Error::EndArchive => 10,
+            Error::NoMemory => 11,
+            Error::BadData => 12,
+            Error::BadArchive => 13,
+            Error::UnknownFormat => 14,
+            Error::Open => 15,
+            Error::Create => 16,
+            Error::Close => 17,
+            Error::Read => 18,
+            Error::Write => 19,
+            Error::SmallBuf => 20,
+            Error::Aborted => 21,
+            Error::NoFiles => 22,
+            Error::TooManyFiles => 23,
+            Error::NotSupported => 24,
+            Error::FilesSkipped => E_FILES_SKIPPED,
+            Error::UnsafePath => E_UNSAFE_PATH,
+        }
+    }
+
+    fn message(self) -> &'static str {
+        match self {
+            Error::EndArchive => "no more files in archive",
+            Error::NoMemory => "not enough memory",
+            Error::BadData => "bad entry data",
+            Error::BadArchive => "bad archive",
+            Error::UnknownFormat => "unknown archive format",
+            Error::Open => "cannot open existing file",
+            Error::Create => "cannot create file",
+            Error::Close => "error closing file",
+            Error::Read => "error reading from file",
+            Error::Write => "error writing to file",
+            Error::SmallBuf => "buffer too small",
+            Error::Aborted => "aborted by user",
+            Error::NoFiles => "no files found",
+            Error::TooManyFiles => "too many files to pack",
+            Error::NotSupported => "not supported",
+            Error::FilesSkipped => "files skipped",
+            Error::UnsafePath => "unsafe entry path",
+        }
+    }
+}
+
+impl From<Error> for c_int {
+    fn from(err: Error) -> c_int {
+        err.code()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl StdError for Error {}
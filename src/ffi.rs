@@ -0,0 +1,814 @@
+// The safety requirements of the exports are the WCX API's, as described on each
+#![allow(clippy::missing_safety_doc)]
+
+use wcxhead::{PackDefaultParamStruct, tOpenArchiveDataW, tOpenArchiveData, tChangeVolProcW, tChangeVolProc, tHeaderDataExW, tHeaderDataEx, tHeaderData,
+              PK_CAPS_BY_CONTENT, PK_CAPS_SEARCHTEXT, PK_CAPS_MULTIPLE, PK_CAPS_OPTIONS, PK_CAPS_MEMPACK, PK_CAPS_DELETE, PK_CAPS_MODIFY, PK_CAPS_NEW,
+              BACKGROUND_MEMPACK, BACKGROUND_UNPACK, BACKGROUND_PACK, E_NOT_SUPPORTED, E_BAD_ARCHIVE, E_END_ARCHIVE, MEMPACK_DONE, E_BAD_DATA, MEMPACK_OK,
+              PK_EXTRACT, PK_PACK_ENCRYPT, PK_PACK_MOVE_FILES, PK_PACK_SAVE_PATHS, MEM_OPTIONS_WANTHEADERS, E_ECLOSE, E_EWRITE, PK_SKIP, PK_TEST};
+use libc::{c_char, c_int};
+use self::super::{ProcessDataProcW, ProcessDataProc, ProgressCallbacks, HandleRegistry, MemPackState, ArchiveState, PackFlags, Settings, Error,
+                  is_valid_archive, current_config, entry_mod_time, modify_archive, pack_archive, catch_panic};
+use self::super::util::{CListIter, system_time_to_totalcmd_time, entry_to_host_path, ansi_to_string, wide_to_string};
+use self::super::header::{EntryHeader, FillHeader};
+#[cfg(windows)]
+use winapi::shared::ntdef::{HANDLE, WCHAR};
+#[cfg(windows)]
+use winapi::shared::minwindef::{HINSTANCE, FALSE, BOOL};
+#[cfg(windows)]
+use winapi::shared::windef::HWND;
+#[cfg(windows)]
+use self::super::dialog;
+#[cfg(not(windows))]
+use wcxhead::{HANDLE, WCHAR, FALSE, BOOL};
+use hrx::HrxEntryData;
+use std::{slice, ptr};
+use std::ffi::CStr;
+use std::path::{PathBuf, Path};
+use std::sync::Mutex;
+
+
+
+/// States of the archives opened with [`OpenArchive`](fn.OpenArchive.html)
+static ARCHIVES: HandleRegistry<ArchiveState> = HandleRegistry::new();
+
+/// States of the in-memory packing operations started with [`StartMemPack`](fn.StartMemPack.html)
+static MEMPACKS: HandleRegistry<MemPackState> = HandleRegistry::new();
+
+/// The ini file the settings were read from by [`PackSetDefaultParams`](fn.PackSetDefaultParams.html), and are saved to
+static INI_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+
+/// OpenArchive should perform all necessary operations when an archive is to be opened.
+///
+/// ```c
+/// HANDLE __stdcall OpenArchive (tOpenArchiveData *ArchiveData);
+/// ```
+///
+/// # Description
+///
+/// OpenArchive should return a unique handle representing the archive. The handle should remain valid until
+/// [CloseArchive](fn.CloseArchive.html) is called.
+/// If an [error](wcxhead/#error-codes) occurs, you should return zero, and specify the [error](wcxhead/#error-codes) by
+/// setting OpenResult member of ArchiveData.
+///
+/// You can use the ArchiveData to query information about the archive being open, and store the information in ArchiveData to
+/// some location that can be accessed via the handle.
+#[no_mangle]
+pub unsafe extern "system" fn OpenArchive(ArchiveData: *mut tOpenArchiveData) -> HANDLE {
+    let ArchiveData = &mut *ArchiveData;
+
+    match catch_panic(Err(E_BAD_ARCHIVE), || OpenArchiveImpl(&CStr::from_ptr(ArchiveData.ArcName).to_string_lossy()[..])) {
+        Ok(handle) => handle,
+        Err(err) => {
+            ArchiveData.OpenResult = err;
+            ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn OpenArchiveW(ArchiveData: *mut tOpenArchiveDataW) -> HANDLE {
+    let ArchiveData = &mut *ArchiveData;
+
+    match catch_panic(Err(E_BAD_ARCHIVE),
+                      || OpenArchiveImpl(wide_to_string(ArchiveData.ArcName))) {
+        Ok(handle) => handle,
+        Err(err) => {
+            ArchiveData.OpenResult = err;
+            ptr::null_mut()
+        }
+    }
+}
+
+fn OpenArchiveImpl<P: AsRef<Path>>(path: P) -> Result<HANDLE, c_int> {
+    OpenArchiveImpl_impl(path.as_ref())
+}
+
+fn OpenArchiveImpl_impl(path: &Path) -> Result<HANDLE, c_int> {
+    ArchiveState::open(path, &current_config()).map(|arch| ARCHIVES.insert(arch) as HANDLE).map_err(Error::code)
+}
+
+
+/// Totalcmd calls ReadHeader to find out what files are in the archive.
+///
+/// ```c
+/// int __stdcall ReadHeader (HANDLE hArcData, tHeaderData *HeaderData);
+/// ```
+///
+/// # Description
+///
+/// ReadHeader is called as long as it returns zero (as long as the previous call to this function returned zero). Each time it
+/// is called, `HeaderData` is supposed to provide Totalcmd with information about the next file contained in the archive. When
+/// all files in the archive have been returned, ReadHeader should return E_END_ARCHIVE which will prevent ReaderHeader from
+/// being called again. If an error occurs, ReadHeader should return one of the [error values](wcxhead/#error-codes)
+/// or 0 for no error.
+///
+/// `hArcData` contains the handle returned by [`OpenArchive`](fn.OpenArchive.html). The programmer is encouraged to store
+/// other information in the location that can be accessed via this handle. For example, you may want to store the position in
+/// the archive when returning files information in ReadHeader.
+///
+/// In short, you are supposed to set at least PackSize, UnpSize, FileTime, and FileName members of tHeaderData. Totalcmd will
+/// use this information to display content of the archive when the archive is viewed as a directory.
+///
+/// Entries over 2 GiB are reported as `INT_MAX` bytes long here, since only [`ReadHeaderEx`](fn.ReadHeaderEx.html) can describe
+/// them.
+#[no_mangle]
+pub unsafe extern "system" fn ReadHeader(hArcData: HANDLE, HeaderData: *mut tHeaderData) -> c_int {
+    let HeaderData = &mut *HeaderData;
+
+    catch_panic(E_BAD_ARCHIVE, move || ReadHeaderImpl(hArcData, |header| HeaderData.fill_header(header)))
+}
+
+/// Totalcmd calls ReadHeaderEx to find out what files are in the archive. This function is always called instead of ReadHeader
+/// if it is present. It only needs to be implemented if the supported archive type may contain files >2 GB. You should
+/// implement both ReadHeader and ReadHeaderEx in this case, for compatibility with older versions of Total Commander.
+///
+/// ```c
+/// int __stdcall ReadHeaderEx (HANDLE hArcData, tHeaderDataEx *HeaderDataEx);
+/// ```
+///
+/// # Description
+///
+/// ReadHeaderEx is called as long as it returns zero (as long as the previous call to this function returned zero). Each time
+/// it is called, `HeaderDataEx` is supposed to provide Totalcmd with information about the next file contained in the archive.
+/// When all files in the archive have been returned, ReadHeaderEx should return E_END_ARCHIVE which will prevent
+/// ReaderHeaderEx from being called again. If an error occurs, ReadHeaderEx should return one of the
+/// [error values](wcxhead/#error-codes) or 0 for no error.
+///
+/// `hArcData` contains the handle returned by [`OpenArchive`](fn.OpenArchive.html). The programmer is encouraged to store
+/// other information in the
+/// location that can be accessed via this handle. For example, you may want to store the position in the archive when
+/// returning files information in ReadHeaderEx.
+///
+/// In short, you are supposed to set at least PackSize, PackSizeHigh, UnpSize, UnpSizeHigh, FileTime, and FileName members of
+/// tHeaderDataEx. Totalcmd will use this information to display content of the archive when the archive is viewed as a
+/// directory.
+#[no_mangle]
+pub unsafe extern "system" fn ReadHeaderEx(hArcData: HANDLE, HeaderDataEx: *mut tHeaderDataEx) -> c_int {
+    let HeaderDataEx = &mut *HeaderDataEx;
+
+    catch_panic(E_BAD_ARCHIVE, move || ReadHeaderImpl(hArcData, |header| HeaderDataEx.fill_header(header)))
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn ReadHeaderExW(hArcData: HANDLE, HeaderDataEx: *mut tHeaderDataExW) -> c_int {
+    let HeaderDataEx = &mut *HeaderDataEx;
+
+    catch_panic(E_BAD_ARCHIVE, move || ReadHeaderImpl(hArcData, |header| HeaderDataEx.fill_header(header)))
+}
+
+fn ReadHeaderImpl<F: FnOnce(&EntryHeader)>(hArcData: HANDLE, callback: F) -> c_int {
+    ARCHIVES.with(hArcData as usize, |state| ReadHeaderImpl_impl(state, callback)).unwrap_or(E_BAD_ARCHIVE)
+}
+
+fn ReadHeaderImpl_impl<F: FnOnce(&EntryHeader)>(state: &mut ArchiveState, callback: F) -> c_int {
+    let mod_time = state.mod_time;
+    if state.next_entry().is_none() {
+        return E_END_ARCHIVE;
+    }
+
+    // Bodies that don't decode will fail extraction anyway
+    let crc = state.current_crc().unwrap_or(0);
+    let size = state.current_size().ok();
+    match state.current_entry() {
+        Some((_, entry, fname)) => {
+            let mod_time = entry_mod_time(entry).unwrap_or(mod_time);
+            let (attr, entry_body) = match &entry.data {
+                HrxEntryData::File { body } => (0x00, body.as_ref().map(|s| &s[..]).unwrap_or("")),
+                HrxEntryData::Directory => (0x10, ""),
+            };
+
+            // Unsafe names are listed as they are, and refused when extracting
+            callback(&EntryHeader {
+                name: &entry_to_host_path(fname),
+                packed_size: entry_body.len() as u64,
+                size: size.unwrap_or(entry_body.len() as u64),
+                file_time: system_time_to_totalcmd_time(&mod_time),
+                attr: attr,
+                crc: crc,
+            });
+
+            0
+        }
+        None => E_END_ARCHIVE,
+    }
+}
+
+
+/// ProcessFile should unpack the specified file or test the integrity of the archive.
+///
+/// ```c
+/// int __stdcall ProcessFile (HANDLE hArcData, int Operation, char *DestPath, char *DestName);
+/// ```
+///
+/// # Description
+///
+/// ProcessFile should return zero on success, or one of the [error values](wcxhead/#error-codes) otherwise.
+///
+/// `hArcData` contains the handle previously returned by you in [`OpenArchive`](fn.OpenArchive.html). Using this, you should
+/// be able to find out
+/// information (such as the archive filename) that you need for extracting files from the archive.
+///
+/// Unlike [`PackFiles`](fn.PackFiles.html), ProcessFile is passed only one filename. Either `DestName` contains the full path
+/// and file name and `DestPath` is NULL, or `DestName` contains only the file name and `DestPath` the file path. This is done
+/// for compatibility with unrar.dll.
+///
+/// When Total Commander first opens an archive, it scans all file names with OpenMode==PK_OM_LIST, so ReadHeader() is called
+/// in a loop with calling ProcessFile(...,PK_SKIP,...). When the user has selected some files and started to decompress them,
+/// Total Commander again calls ReadHeader() in a loop. For each file which is to be extracted, Total Commander calls
+/// ProcessFile() with Operation==PK_EXTRACT immediately after the ReadHeader() call for this file. If the file needs to be
+/// skipped, it calls it with Operation==PK_SKIP.
+///
+/// Each time `DestName` is set to contain the filename to be extracted, tested, or skipped. To find out what operation out of
+/// these last three you should apply to the current file within the archive, `Operation` is set to one of the following:
+///
+/// Constant   | Value | Description
+/// --------   | ----- | -----------
+/// PK_SKIP    | 0     | Skip this file
+/// PK_TEST    | 1     | Test file integrity
+/// PK_EXTRACT | 2     | Extract to disk
+#[no_mangle]
+pub unsafe extern "system" fn ProcessFile(hArcData: HANDLE, Operation: c_int, DestPath: *mut c_char, DestName: *mut c_char) -> c_int {
+    // That is a lie, both DestPath and DestName are NULL when Operation==PK_SKIP
+
+    let DestPath = if !DestPath.is_null() {
+        Some(CStr::from_ptr(DestPath).to_string_lossy())
+    } else {
+        None
+    };
+
+    let DestName = if !DestName.is_null() {
+        Some(CStr::from_ptr(DestName).to_string_lossy())
+    } else {
+        None
+    };
+
+    catch_panic(ProcessFilePanicError(Operation), || {
+        ProcessFileImpl(hArcData,
+                        Operation,
+                        DestPath.as_ref().map(|s| Path::new(&s[..])),
+                        DestName.as_ref().map(|s| Path::new(&s[..])))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn ProcessFileW(hArcData: HANDLE, Operation: c_int, DestPath: *mut WCHAR, DestName: *mut WCHAR) -> c_int {
+    catch_panic(ProcessFilePanicError(Operation), || {
+        ProcessFileImpl(hArcData,
+                        Operation,
+                        if !DestPath.is_null() {
+                            Some(wide_to_string(DestPath))
+                        } else {
+                            None
+                        },
+                        if !DestName.is_null() {
+                            Some(wide_to_string(DestName))
+                        } else {
+                            None
+                        })
+    })
+}
+
+fn ProcessFileImpl<Pd: AsRef<Path>, Pn: AsRef<Path>>(hArcData: HANDLE, Operation: c_int, dest_path: Option<Pd>, dest_name: Option<Pn>) -> c_int {
+    ARCHIVES.with(hArcData as usize,
+                  |state| ProcessFileImpl_impl(state, Operation, dest_path.as_ref().map(AsRef::as_ref), dest_name.as_ref().map(AsRef::as_ref)))
+        .unwrap_or(E_BAD_ARCHIVE)
+}
+
+fn ProcessFileImpl_impl(state: &mut ArchiveState, Operation: c_int, dest_path: Option<&Path>, dest_name: Option<&Path>) -> c_int {
+    match Operation {
+        PK_SKIP => 0,
+        PK_TEST => {
+            match state.test_current_entry() {
+                Ok(()) => 0,
+                Err((err, _)) => err.code(),
+            }
+        }
+        PK_EXTRACT => {
+            match state.extract_current_entry(dest_path, dest_name) {
+                Ok(()) => 0,
+                Err(err) => err.code(),
+            }
+        }
+        _ => E_NOT_SUPPORTED,
+    }
+}
+
+/// The error reported if the specified operation panics
+fn ProcessFilePanicError(Operation: c_int) -> c_int {
+    match Operation {
+        PK_TEST => E_BAD_DATA,
+        PK_EXTRACT => E_EWRITE,
+        _ => E_BAD_ARCHIVE,
+    }
+}
+
+
+/// CloseArchive should perform all necessary operations when an archive is about to be closed.
+///
+/// ```c
+/// int __stdcall CloseArchive (HANDLE hArcData);
+/// ```
+///
+/// # Description
+///
+/// CloseArchive should return zero on success, or one of the [error values](wcxhead/#error-codes) otherwise. It should free
+/// all the resources
+/// associated with the open archive.
+///
+/// The parameter `hArcData` refers to the value returned by a programmer within a previous call to
+/// [`OpenArchive`](fn.OpenArchive.html).
+#[no_mangle]
+pub extern "system" fn CloseArchive(hArcData: HANDLE) -> c_int {
+    catch_panic(E_ECLOSE, || if ARCHIVES.remove(hArcData as usize) { 0 } else { E_ECLOSE })
+}
+
+
+/// This function allows you to notify user about changing a volume when packing files.
+///
+/// ```c
+/// void __stdcall SetChangeVolProc (HANDLE hArcData, tChangeVolProc pChangeVolProc1);
+/// ```
+///
+/// # Description
+///
+/// `pChangeVolProc1` contains a pointer to a function that you may want to call when notifying user to change volume (e.g.
+/// insterting another diskette). You need to store the value at some place if you want to use it; you can use `hArcData` that
+/// you have returned by [`OpenArchive`](fn.OpenArchive.html) to identify that place.
+#[no_mangle]
+pub extern "system" fn SetChangeVolProc(_: HANDLE, _: tChangeVolProc) {}
+
+#[no_mangle]
+pub extern "system" fn SetChangeVolProcW(_: HANDLE, _: tChangeVolProcW) {}
+
+
+/// This function allows you to notify user about the progress when you un/pack files.
+///
+/// ```c
+/// void __stdcall SetProcessDataProc (HANDLE hArcData, tProcessDataProc pProcessDataProc);
+/// ```
+///
+/// # Description
+///
+/// `pProcessDataProc` contains a pointer to a function that you may want to call when notifying user about the progress being
+/// made when you pack or extract files from an archive. You need to store the value at some place if you want to use it; you
+/// can use `hArcData` that you have returned by [`OpenArchive`](fn.OpenArchive.html) to identify that place.
+#[no_mangle]
+pub extern "system" fn SetProcessDataProc(hArcData: HANDLE, pProcessDataProc: ProcessDataProc) {
+    catch_panic((), || if hArcData.is_null() || hArcData as usize == usize::MAX {
+        // INVALID_HANDLE_VALUE, for the next PackFiles() or DeleteFiles() call, made from the same thread
+        let mut callbacks = ProgressCallbacks::for_thread();
+        callbacks.ansi = Some(pProcessDataProc);
+        callbacks.set_for_thread();
+    } else {
+        ARCHIVES.with(hArcData as usize, |state| state.progress.ansi = Some(pProcessDataProc));
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn SetProcessDataProcW(hArcData: HANDLE, pProcessDataProc: ProcessDataProcW) {
+    catch_panic((), || if hArcData.is_null() || hArcData as usize == usize::MAX {
+        // INVALID_HANDLE_VALUE, for the next PackFiles() or DeleteFiles() call, made from the same thread
+        let mut callbacks = ProgressCallbacks::for_thread();
+        callbacks.wide = Some(pProcessDataProc);
+        callbacks.set_for_thread();
+    } else {
+        ARCHIVES.with(hArcData as usize, |state| state.progress.wide = Some(pProcessDataProc));
+    })
+}
+
+
+/// PackFiles specifies what should happen when a user creates, or adds files to the archive.
+///
+/// ```c
+/// int __stdcall PackFiles (char *PackedFile, char *SubPath, char *SrcPath, char *AddList, int Flags);
+/// ```
+///
+/// # Description
+///
+/// PackFiles should return zero on success, or one of the [error values](wcxhead/#error-codes) otherwise.
+///
+/// `PackedFile` refers to the archive that is to be created or modified. The string contains the full path.
+///
+/// `SubPath` is either NULL, when the files should be packed with the paths given with the file names, or not NULL when they
+/// should be placed below the given subdirectory within the archive. Example:
+///
+/// ```plaintext
+/// SubPath="subdirectory"
+/// Name in AddList="subdir2\filename.ext"
+/// -> File should be packed as "subdirectory\subdir2\filename.ext"
+/// ```
+///
+/// `SrcPath` contains path to the files in `AddList`. `SrcPath` and `AddList` together specify files that are to be packed into
+/// `PackedFile`. Each string in `AddList` is zero-delimited (ends in zero), and the `AddList` string ends with an extra zero
+/// byte, i.e. there are two zero bytes at the end of `AddList`.
+///
+/// `Flags` can contain a combination of the following values reflecting the user choice from within Totalcmd:
+///
+/// | Constant           | Value | Description                                                 |
+/// | --------           | ----- | -----------                                                 |
+/// | PK_PACK_MOVE_FILES | 1     | Delete original after packing                               |
+/// | PK_PACK_SAVE_PATHS | 2     | Save path names of files                                    |
+/// | PK_PACK_ENCRYPT    | 4     | Ask user for password, then encrypt file with that password |
+///
+/// Files skipped as per the `BinaryPolicy` setting are left out of the archive, which is still written with the rest, and
+/// [`E_FILES_SKIPPED`](constant.E_FILES_SKIPPED.html) is returned.
+#[no_mangle]
+pub unsafe extern "system" fn PackFiles(PackedFile: *mut c_char, SubPath: *mut c_char, SrcPath: *mut c_char, AddList: *mut c_char, Flags: c_int) -> c_int {
+    catch_panic(E_EWRITE, || {
+        let progress = ProgressCallbacks::for_thread_operation();
+
+        let flags = match PackFilesFlags(Flags) {
+            Ok(flags) => flags,
+            Err(err) => return err.code(),
+        };
+
+        match pack_archive(CStr::from_ptr(PackedFile).to_string_lossy().into_owned(),
+                           if SubPath.is_null() {
+                               None
+                           } else {
+                               Some(CStr::from_ptr(SubPath).to_string_lossy())
+                           },
+                           &CStr::from_ptr(SrcPath).to_string_lossy()[..],
+                           CListIter(AddList)
+                               .map(|s| CStr::from_bytes_with_nul_unchecked(slice::from_raw_parts(s.as_ptr() as *const u8, s.len() + 1)))
+                               .map(|s| s.to_string_lossy()),
+                           flags,
+                           &current_config(),
+                           &progress) {
+            Ok(skipped) if skipped.is_empty() => 0,
+            Ok(_) => Error::FilesSkipped.code(),
+            Err(err) => err.code(),
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn PackFilesW(PackedFile: *mut WCHAR, SubPath: *mut WCHAR, SrcPath: *mut WCHAR, AddList: *mut WCHAR, Flags: c_int) -> c_int {
+    catch_panic(E_EWRITE, || {
+        let progress = ProgressCallbacks::for_thread_operation();
+
+        let flags = match PackFilesFlags(Flags) {
+            Ok(flags) => flags,
+            Err(err) => return err.code(),
+        };
+
+        match pack_archive(wide_to_string(PackedFile),
+                           if SubPath.is_null() {
+                               None
+                           } else {
+                               Some(wide_to_string(SubPath))
+                           },
+                           wide_to_string(SrcPath),
+                           CListIter(AddList).map(String::from_utf16_lossy),
+                           flags,
+                           &current_config(),
+                           &progress) {
+            Ok(skipped) if skipped.is_empty() => 0,
+            Ok(_) => Error::FilesSkipped.code(),
+            Err(err) => err.code(),
+        }
+    })
+}
+
+fn PackFilesFlags(Flags: c_int) -> Result<PackFlags, Error> {
+    if (Flags & PK_PACK_ENCRYPT) != 0 {
+        return Err(Error::NotSupported);
+    }
+
+    Ok(PackFlags {
+        move_files: (Flags & PK_PACK_MOVE_FILES) != 0,
+        save_paths: (Flags & PK_PACK_SAVE_PATHS) != 0,
+    })
+}
+
+
+/// DeleteFiles should delete the specified files from the archive
+///
+/// ```c
+/// int __stdcall DeleteFiles (char *PackedFile, char *DeleteList);
+/// ```
+///
+/// # Description
+///
+/// DeleteFiles should return zero on success, or one of the [error values](wcxhead/#error-codes) otherwise.
+///
+/// `PackedFile` contains full path and name of the the archive.
+///
+/// `DeleteList` contains the list of files that should be deleted from the archive. The format of this string is the same as
+/// `AddList` within [PackFiles](fn.PackFiles.html).
+#[no_mangle]
+pub unsafe extern "system" fn DeleteFiles(PackedFile: *mut c_char, DeleteList: *mut c_char) -> c_int {
+    catch_panic(E_EWRITE, || {
+        let progress = ProgressCallbacks::for_thread_operation();
+
+        match modify_archive(CStr::from_ptr(PackedFile).to_string_lossy().into_owned(),
+                             CListIter(DeleteList)
+                                 .map(|s| CStr::from_bytes_with_nul_unchecked(slice::from_raw_parts(s.as_ptr() as *const u8, s.len() + 1)))
+                                 .map(|s| s.to_string_lossy()),
+                             &current_config(),
+                             &progress) {
+            Ok(()) => 0,
+            Err(err) => err.code(),
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn DeleteFilesW(PackedFile: *mut WCHAR, DeleteList: *mut WCHAR) -> c_int {
+    catch_panic(E_EWRITE, || {
+        let progress = ProgressCallbacks::for_thread_operation();
+
+        match modify_archive(wide_to_string(PackedFile),
+                             CListIter(DeleteList).map(String::from_utf16_lossy),
+                             &current_config(),
+                             &progress) {
+            Ok(()) => 0,
+            Err(err) => err.code(),
+        }
+    })
+}
+
+
+/// GetPackerCaps tells Totalcmd what features your packer plugin supports.
+///
+/// ```c
+/// int __stdcall GetPackerCaps();
+/// ```
+///
+/// # Description
+///
+/// Implement GetPackerCaps to return a combination of the following values:
+///
+/// | Constant           | Value | Description                                                      |
+/// | --------           | ----- | -----------                                                      |
+/// | PK_CAPS_NEW        | 1     | Can create new archives                                          |
+/// | PK_CAPS_MODIFY     | 2     | Can modify existing archives                                     |
+/// | PK_CAPS_MULTIPLE   | 4     | Archive can contain multiple files                               |
+/// | PK_CAPS_DELETE     | 8     | Can delete files                                                 |
+/// | PK_CAPS_OPTIONS    | 16    | Has options dialog                                               |
+/// | PK_CAPS_MEMPACK    | 32    | Supports packing in memory                                       |
+/// | PK_CAPS_BY_CONTENT | 64    | Detect archive type by content                                   |
+/// | PK_CAPS_SEARCHTEXT | 128   | Allow searching for text in archives created with this plugin    |
+/// | PK_CAPS_HIDE       | 256   | Don't show packer icon, don't open with Enter but with Ctrl+PgDn |
+/// | PK_CAPS_ENCRYPT    | 512   | Plugin supports encryption.                                      |
+///
+/// Omitting PK_CAPS_NEW and PK_CAPS_MODIFY means [PackFiles](fn.PackFiles.html) will never be called and so you don’t have to
+/// implement [PackFiles](fn.PackFiles.html). Omitting PK_CAPS_MULTIPLE means [PackFiles](fn.PackFiles.html) will be supplied
+/// with just one file. Leaving out PK_CAPS_DELETE means [DeleteFiles](fn.DeleteFiles.html) will never be called; leaving out
+/// PK_CAPS_OPTIONS means [ConfigurePacker](fn.ConfigurePacker.html) will not be called. PK_CAPS_MEMPACK enables the functions
+/// [StartMemPack](fn.StartMemPack.html), [PackToMem](fn.PackToMem.html) and [DoneMemPack](fn.DoneMemPack.html). If
+/// PK_CAPS_BY_CONTENT is returned, Totalcmd calls the function [CanYouHandleThisFile](fn.CanYouHandleThisFile.html) when the
+/// user presses Ctrl+PageDown on an unknown archive type. Finally, if PK_CAPS_SEARCHTEXT is returned, Total Commander will
+/// search for text inside files packed with this plugin. This may not be a good idea for certain plugins like the diskdir
+/// plugin, where file contents may not be available. If PK_CAPS_HIDE is set, the plugin will not show the file type as a
+/// packer. This is useful for plugins which are mainly used for creating files, e.g. to create batch files, avi files etc. The
+/// file needs to be opened with Ctrl+PgDn in this case, because Enter will launch the associated application.
+///
+/// Important note:
+///
+/// If you change the return values of this function, e.g. add packing support, you need to reinstall the packer plugin in
+/// Total Commander, otherwise it will not detect the new capabilities.
+///
+/// PK_CAPS_OPTIONS is only returned on Windows; elsewhere, the settings are edited in the ini file.
+#[no_mangle]
+pub extern "system" fn GetPackerCaps() -> c_int {
+    let options = if cfg!(windows) { PK_CAPS_OPTIONS } else { 0 };
+    PK_CAPS_NEW | PK_CAPS_MODIFY | PK_CAPS_MULTIPLE | PK_CAPS_DELETE | options | PK_CAPS_MEMPACK | PK_CAPS_BY_CONTENT | PK_CAPS_SEARCHTEXT
+}
+
+
+/// ConfigurePacker gets called when the user clicks the Configure button from within "Pack files..." dialog box in Totalcmd.
+///
+/// ```c
+/// void __stdcall ConfigurePacker (HWND Parent, HINSTANCE DllInstance);
+/// ```
+///
+/// # Description
+///
+/// `Parent` is the parent window, and `DllInstance` the instance handle of the plugin DLL, for loading resources.
+///
+/// Usually, you provide a user with a dialog box where he can configure the packer.
+///
+/// The settings are edited in a dialog with a control per [field](struct.Settings.html#associatedconstant.FIELDS), then saved
+/// to the ini file passed to [PackSetDefaultParams](fn.PackSetDefaultParams.html) and applied.
+#[cfg(windows)]
+#[no_mangle]
+pub extern "system" fn ConfigurePacker(Parent: HWND, DllInstance: HINSTANCE) {
+    catch_panic((), || {
+        let ini_path = INI_PATH.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        match ini_path {
+            Some(ini_path) => {
+                if let Ok(Some(settings)) = Settings::edit(&ini_path, |s| dialog::edit_settings(Parent, DllInstance, s)) {
+                    settings.apply(ini_path.parent().unwrap_or_else(|| Path::new("")));
+                }
+            }
+            None => {
+                let mut settings = Settings { config: current_config(), ..Settings::default() };
+                if dialog::edit_settings(Parent, DllInstance, &mut settings) {
+                    settings.apply(Path::new(""));
+                }
+            }
+        }
+    })
+}
+
+
+/// CanYouHandleThisFile allows the plugin to handle files with different extensions than the one defined in Total Commander.
+/// It is called when the plugin defines PK_CAPS_BY_CONTENT, and the user tries to open an archive with Ctrl+PageDown.
+///
+/// ```c
+/// BOOL __stdcall CanYouHandleThisFile (char *FileName);
+/// ```
+///
+/// # Description
+///
+/// CanYouHandleThisFile should return true (nonzero) if the plugin recognizes the file as an archive which it can handle. The
+/// detection must be by contents, NOT by extension. If this function is not implemented, Totalcmd assumes that only files with
+/// a given extension can be handled by the plugin.
+///
+/// `Filename` contains the fully qualified name (path+name) of the file to be checked.
+#[no_mangle]
+pub unsafe extern "system" fn CanYouHandleThisFile(FileName: *mut c_char) -> BOOL {
+    catch_panic(FALSE, || is_valid_archive(&CStr::from_ptr(FileName).to_string_lossy()[..]) as BOOL)
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn CanYouHandleThisFileW(FileName: *mut WCHAR) -> BOOL {
+    catch_panic(FALSE, || is_valid_archive(wide_to_string(FileName)) as BOOL)
+}
+
+
+/// StartMemPack starts packing into memory. This function is only needed if you want to create archives in combination with
+/// TrueCrypt or similar tools, where the packed data isn't written to a file directly.
+///
+/// ```c
+/// HANDLE __stdcall StartMemPack (int Options, char *FileName);
+/// ```
+///
+/// # Description
+///
+/// StartMemPack should return a user-defined handle (e.g. pointer to a structure) on success, or zero on error.
+///
+/// `Options` can contain a combination of the following values:
+///
+/// | Constant                | Value | Description                              |
+/// | --------                | ----- | -----------                              |
+/// | MEM_OPTIONS_WANTHEADERS | 1     | Return archive headers with packed data  |
+///
+/// `FileName` contains the name of the file being packed – this info may be used for embedding the name in the archive.
+///
+/// Packing isn't streamed: all the data passed to [PackToMem](fn.PackToMem.html) is buffered until it signals the end of input.
+/// Without MEM_OPTIONS_WANTHEADERS, only the entry body is returned, with its line endings unconverted, and data that'd have to
+/// be base64-encoded is refused.
+#[no_mangle]
+pub unsafe extern "system" fn StartMemPack(Options: c_int, FileName: *mut c_char) -> HANDLE {
+    catch_panic(ptr::null_mut(), || {
+        match MemPackState::new((Options & MEM_OPTIONS_WANTHEADERS) != 0, CStr::from_ptr(FileName).to_string_lossy(), &current_config()) {
+            Ok(state) => MEMPACKS.insert(state) as HANDLE,
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+
+/// PackToMem packs the next chunk of data passed to it and/or returns the packed data to the calling program.
+///
+/// ```c
+/// int __stdcall PackToMem (HANDLE hMemPack, char *BufIn, int InLen, int *Taken, char *BufOut, int OutLen, int *Written,
+///                          int SeekBy);
+/// ```
+///
+/// # Description
+///
+/// PackToMem should return MEMPACK_OK (0) on success, MEMPACK_DONE (1) when all the packed data has been returned, or one of
+/// the [error values](wcxhead/#error-codes) otherwise.
+///
+/// PackToMem is called in a loop as long as it returns MEMPACK_OK. `BufIn` points to `InLen` bytes of data to be packed;
+/// the number of bytes taken from it is stored in `Taken`, and the remaining bytes will be passed again in a later call.
+/// When there is no more data to be packed, `InLen` is set to zero. `BufOut` points to a buffer of `OutLen` bytes which can
+/// receive the packed data, the number of bytes placed there is stored in `Written`.
+///
+/// All the input is buffered, and nothing is written to `BufOut` until the call with `InLen` set to zero.
+///
+/// `SeekBy` would allow seeking in the output before writing; HRX bodies are preceded by their headers, so it's never needed.
+#[no_mangle]
+pub unsafe extern "system" fn PackToMem(hMemPack: HANDLE, BufIn: *mut c_char, InLen: c_int, Taken: *mut c_int, BufOut: *mut c_char, OutLen: c_int,
+                                         Written: *mut c_int, _: c_int)
+                                         -> c_int {
+    catch_panic(E_EWRITE, || {
+        let BufIn = if !BufIn.is_null() && InLen > 0 {
+            slice::from_raw_parts(BufIn as *const u8, InLen as usize)
+        } else {
+            &[]
+        };
+        let BufOut = if !BufOut.is_null() && OutLen > 0 {
+            slice::from_raw_parts_mut(BufOut as *mut u8, OutLen as usize)
+        } else {
+            &mut []
+        };
+
+        match MEMPACKS.with(hMemPack as usize, |state| state.pack_to_mem(BufIn, BufOut)) {
+            Some(Ok((taken, written, done))) => {
+                *Taken = taken as c_int;
+                *Written = written as c_int;
+
+                if done { MEMPACK_DONE } else { MEMPACK_OK }
+            }
+            Some(Err(err)) => err.code(),
+            None => E_BAD_ARCHIVE,
+        }
+    })
+}
+
+
+/// DoneMemPack ends packing into memory.
+///
+/// ```c
+/// int __stdcall DoneMemPack (HANDLE hMemPack);
+/// ```
+///
+/// # Description
+///
+/// DoneMemPack should return zero on success, or one of the [error values](wcxhead/#error-codes) otherwise. It should free
+/// all the resources associated with `hMemPack`, the handle returned by [`StartMemPack`](fn.StartMemPack.html).
+///
+/// DoneMemPack is also called when the user aborts the operation, in which case the packed data isn't needed anymore.
+#[no_mangle]
+pub extern "system" fn DoneMemPack(hMemPack: HANDLE) -> c_int {
+    catch_panic(E_ECLOSE, || if MEMPACKS.remove(hMemPack as usize) { 0 } else { E_ECLOSE })
+}
+
+
+/// GetBackgroundFlags is called to determine whether a plugin supports background packing or unpacking.
+///
+/// ```c
+/// int __stdcall GetBackgroundFlags(void);
+/// ```
+///
+/// # Description
+///
+/// GetBackgroundFlags should return one of the following values:
+///
+/// <table>
+///   <thead><tr><th>Constant</th><th>Value</th><th>Description</th></tr></thead>
+///   <tbody>
+///     <tr><td>BACKGROUND_UNPACK</td>
+///         <td>1</td>
+///         <td>Calls to OpenArchive, ReadHeader(Ex), ProcessFile and CloseArchive are thread-safe
+///             (unpack in background)</td></tr>
+///     <tr><td>BACKGROUND_PACK</td>
+///         <td>2</td>
+///         <td>Calls to PackFiles are thread-safe (pack in background)</td></tr>
+///     <tr><td>BACKGROUND_MEMPACK</td>
+///         <td>4</td>
+///         <td>Calls to StartMemPack, PackToMem and DoneMemPack are thread-safe</td></tr>
+///   </tbody>
+/// </table>
+///
+/// # Notes
+///
+/// To make your packer plugin thread-safe, you should remove any global variables which aren't the same for all pack or unpack
+/// operations. For example, the path to the ini file name can remain global, but something like the compression ratio, or file
+/// handles need to be stored separately.
+///
+/// **Packing**: The PackFiles function is just a single call, so you can store all variables on the stack (local variables of
+/// that function).
+///
+/// **Unpacking**: You can allocate a struct containing all the variables you need across function calls, like the compression
+/// method and ratio, and state variables, and return a pointer to this struct as a result to OpenArchive. This pointer will
+/// then passed to all other functions like ReadHeader as parameter hArcData.
+///
+/// **Pack in memory**: You can do the same in StartMemPack as described under Unpacking.
+#[no_mangle]
+pub extern "system" fn GetBackgroundFlags() -> c_int {
+    BACKGROUND_UNPACK | BACKGROUND_PACK | BACKGROUND_MEMPACK
+}
+
+
+/// PackSetDefaultParams is called immediately after loading the DLL, before any other function. This function is new in
+/// version 2.1. It requires Total Commander >=5.51, but is ignored by older versions.
+///
+/// ```c
+/// void __stdcall PackSetDefaultParams(PackDefaultParamStruct* dps);
+/// ```
+///
+/// # Description
+///
+/// `dps` is a pointer to a structure of type [PackDefaultParamStruct](wcxhead/struct.PackDefaultParamStruct.html),
+/// containing the suggested location of the plugin's ini file.
+///
+/// The settings are read from its `[totalcmd-hrx]` section, which is written with the defaults if not there yet, and saved
+/// there by [ConfigurePacker](fn.ConfigurePacker.html).
+#[no_mangle]
+pub unsafe extern "system" fn PackSetDefaultParams(dps: *mut PackDefaultParamStruct) {
+    let ini_name = &(*dps).DefaultIniName;
+    let ini_name = slice::from_raw_parts(ini_name.as_ptr() as *const u8, ini_name.len());
+    let ini_name = ansi_to_string(&ini_name[..ini_name.iter().position(|&c| c == 0).unwrap_or(ini_name.len())]);
+
+    catch_panic((), || {
+        let ini_path = Path::new(&ini_name);
+        Settings::load_or_init(ini_path).apply(ini_path.parent().unwrap_or_else(|| Path::new("")));
+        *INI_PATH.lock().unwrap_or_else(|e| e.into_inner()) = Some(ini_path.to_path_buf());
+    })
+}
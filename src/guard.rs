@@ -0,0 +1,76 @@
+use self::super::util::system_time_to_rfc3339;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{PathBuf, Path};
+use std::fs::OpenOptions;
+use std::time::SystemTime;
+#[cfg(feature = "panic-injection")]
+use std::cell::Cell;
+use std::sync::Mutex;
+use std::io::Write;
+use std::any::Any;
+
+
+static PANIC_LOG: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+#[cfg(feature = "panic-injection")]
+thread_local! {
+    /// Message the next call to [`catch_panic()`](fn.catch_panic.html) on this thread panics with, as set by [`inject_panic()`](fn.inject_panic.html)
+    static INJECTED_PANIC: Cell<Option<&'static str>> = const { Cell::new(None) };
+}
+
+
+/// Set the file panics caught by [`catch_panic()`](fn.catch_panic.html) are appended to, or, with `None`, stop logging them
+pub fn set_panic_log<P: AsRef<Path>>(path: Option<P>) {
+    *PANIC_LOG.lock().unwrap_or_else(|e| e.into_inner()) = path.map(|p| p.as_ref().to_path_buf());
+}
+
+/// Make the next export called on the current thread panic with the specified message, as though it ran into a bug
+///
+/// Only meant for testing what the host sees when that happens, and so only built with the `panic-injection` feature.
+#[cfg(feature = "panic-injection")]
+pub fn inject_panic(message: &'static str) {
+    INJECTED_PANIC.with(|p| p.set(Some(message)))
+}
+
+/// Run the specified function, returning `on_panic` instead if it panics
+///
+/// Unwinding into the host is undefined behaviour and, in practice, takes the whole file manager down with it, so every export
+/// goes through here. The panic message is appended to the [panic log](fn.set_panic_log.html), if set.
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::catch_panic;
+/// assert_eq!(catch_panic(19, || 0), 0);
+/// assert_eq!(catch_panic(19, || -> i32 { panic!("oh no") }), 19);
+/// ```
+pub fn catch_panic<R, F: FnOnce() -> R>(on_panic: R, f: F) -> R {
+    match panic::catch_unwind(AssertUnwindSafe(|| {
+        #[cfg(feature = "panic-injection")]
+        {
+            if let Some(message) = INJECTED_PANIC.with(Cell::take) {
+                panic!("{}", message);
+            }
+        }
+        f()
+    })) {
+        Ok(ret) => ret,
+        Err(payload) => {
+            log_panic(&*payload);
+            on_panic
+        }
+    }
+}
+
+fn log_panic(payload: &(dyn Any + Send)) {
+    let message = payload.downcast_ref::<&str>()
+        .cloned()
+        .or_else(|| payload.downcast_ref::<String>().map(|s| &s[..]))
+        .unwrap_or("unknown panic payload");
+
+    if let Some(path) = PANIC_LOG.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        if let Ok(mut log) = OpenOptions::new().create(true).append(true).open(path) {
+            let _ = writeln!(log, "{} panicked: {}", system_time_to_rfc3339(&SystemTime::now()), message);
+        }
+    }
+}
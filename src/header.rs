@@ -0,0 +1,124 @@
+use wcxhead::{tHeaderDataExW, tHeaderDataEx, tHeaderData};
+use self::super::util::{saturate_size, split_size};
+use libc::{c_char, c_int};
+
+
+/// What [`ReadHeader`](fn.ReadHeader.html) and friends report about a single entry
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct EntryHeader<'n> {
+    /// Host name of the entry, with `\` separators
+    pub name: &'n str,
+    /// Size of the entry's body, as stored in the archive
+    pub packed_size: u64,
+    /// Size of the data the entry extracts to, which differs from `packed_size` for base64-encoded bodies and restored line endings
+    pub size: u64,
+    /// Modification time, in the DOS format Totalcmd expects
+    pub file_time: c_int,
+    /// `0x10` for directories, `0x00` for files
+    pub attr: c_int,
+    /// CRC-32 of the extracted data
+    pub crc: u32,
+}
+
+/// A header structure that can be filled in from an [`EntryHeader`](struct.EntryHeader.html)
+///
+/// # Examples
+///
+/// ```
+/// # extern crate totalcmd_hrx;
+/// # #[cfg(windows)]
+/// # extern crate wcxhead;
+/// # #[cfg(not(windows))]
+/// # use totalcmd_hrx::wcxhead;
+/// # use totalcmd_hrx::{EntryHeader, FillHeader};
+/// # use wcxhead::tHeaderDataEx;
+/// # use std::mem;
+/// # fn main() {
+/// let mut header: tHeaderDataEx = unsafe { mem::zeroed() };
+/// header.fill_header(&EntryHeader {
+///     name: "dir\\file.txt",
+///     packed_size: 0x1_2345_6789,
+///     size: 0x1_2345_6789,
+///     file_time: 0,
+///     attr: 0x00,
+///     crc: 0xCBF43926,
+/// });
+///
+/// assert_eq!((header.PackSize, header.PackSizeHigh), (0x2345_6789, 0x1));
+/// assert_eq!(header.FileCRC as u32, 0xCBF43926);
+/// assert_eq!(header.FileName[..13].iter().map(|&c| c as u8).collect::<Vec<_>>(), b"dir\\file.txt\0");
+/// # }
+/// ```
+pub trait FillHeader {
+    fn fill_header(&mut self, header: &EntryHeader);
+}
+
+impl FillHeader for tHeaderData {
+    /// Sizes past 2 GiB can't be represented here and are saturated to `c_int::MAX`,
+    /// which is fine, since hosts that know of larger files call `ReadHeaderEx` instead
+    fn fill_header(&mut self, header: &EntryHeader) {
+        self.PackSize = saturate_size(header.packed_size);
+        self.UnpSize = saturate_size(header.size);
+        self.FileTime = header.file_time;
+        fill_name(&mut self.FileName, header.name);
+
+        self.HostOS = 0;
+        self.FileCRC = header.crc as c_int;
+        self.FileAttr = header.attr;
+    }
+}
+
+impl FillHeader for tHeaderDataEx {
+    fn fill_header(&mut self, header: &EntryHeader) {
+        (self.PackSize, self.PackSizeHigh) = split_size(header.packed_size);
+        (self.UnpSize, self.UnpSizeHigh) = split_size(header.size);
+        self.FileTime = header.file_time;
+        fill_name(&mut self.FileName, header.name);
+
+        self.HostOS = 0;
+        self.FileCRC = header.crc as c_int;
+        self.FileAttr = header.attr;
+        self.Reserved = [0; 1024];
+    }
+}
+
+impl FillHeader for tHeaderDataExW {
+    fn fill_header(&mut self, header: &EntryHeader) {
+        (self.PackSize, self.PackSizeHigh) = split_size(header.packed_size);
+        (self.UnpSize, self.UnpSizeHigh) = split_size(header.size);
+        self.FileTime = header.file_time;
+        fill_wide_name(&mut self.FileName, header.name);
+
+        self.HostOS = 0;
+        self.FileCRC = header.crc as c_int;
+        self.FileAttr = header.attr;
+        self.Reserved = [0; 1024];
+    }
+}
+
+
+/// Copy as much of the name as fits, always leaving a NUL terminator
+fn fill_name(out: &mut [c_char], name: &str) {
+    let last_idx = out.len() - 1;
+    let mut written = 0;
+    for (o, b) in out.iter_mut().take(last_idx).zip(name.bytes()) {
+        *o = b as c_char;
+        written += 1;
+    }
+    for o in &mut out[written..] {
+        *o = 0;
+    }
+}
+
+/// [`fill_name()`](fn.fill_name.html), but UTF-16
+fn fill_wide_name(out: &mut [u16], name: &str) {
+    let last_idx = out.len() - 1;
+    let mut written = 0;
+    for (o, c) in out.iter_mut().take(last_idx).zip(name.encode_utf16()) {
+        *o = c;
+        written += 1;
+    }
+    for o in &mut out[written..] {
+        *o = 0;
+    }
+}
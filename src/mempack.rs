@@ -0,0 +1,100 @@
+use hrx::{HrxEntryData, HrxArchive, HrxEntry, HrxPath};
+use self::super::util::{unescape_entry_path, host_to_entry_path, set_comment_tag, archivable_path};
+use self::super::config::Config;
+use self::super::state::{LINE_ENDINGS_TAG, BASE64_TAG};
+use self::super::error::Error;
+use self::super::pack;
+
+
+/// State of a single in-memory packing operation, as started by [`StartMemPack`](fn.StartMemPack.html)
+///
+/// Nothing is streamed: all input is buffered until the host signals its end by passing an empty buffer, after which the
+/// resulting archive is fed out in as many chunks as the host needs.
+pub struct MemPackState {
+    pub want_headers: bool,
+    pub file_name: HrxPath,
+    pub config: Config,
+
+    input: Vec<u8>,
+    output: Option<Vec<u8>>,
+    output_pos: usize,
+}
+
+impl MemPackState {
+    pub fn new<S: AsRef<str>>(want_headers: bool, file_name: S, config: &Config) -> Result<MemPackState, Error> {
+        MemPackState::new_impl(want_headers, file_name.as_ref(), config)
+    }
+
+    fn new_impl(want_headers: bool, file_name: &str, config: &Config) -> Result<MemPackState, Error> {
+        let file_name = host_to_entry_path(file_name);
+        let file_name = unescape_entry_path(&file_name);
+
+        Ok(MemPackState {
+            want_headers: want_headers,
+            file_name: archivable_path(&file_name).ok_or(Error::NotSupported)?,
+            config: *config,
+            input: vec![],
+            output: None,
+            output_pos: 0,
+        })
+    }
+
+    /// Take data from `buf_in` and write packed data into `buf_out`
+    ///
+    /// An empty `buf_in` marks the end of input, and no output is produced before then. Input that isn't valid UTF-8 is handled as
+    /// per `config.binary_policy`, like the files packed by [`pack_archive()`](fn.pack_archive.html), failing with
+    /// `Error::UnknownFormat` if it's to be skipped.
+    ///
+    /// Without headers, there's nowhere to record base64 encoding or converted line endings, so the body is output with its line
+    /// endings as they are, and input that'd have to be base64-encoded fails with `Error::UnknownFormat`.
+    ///
+    /// Returns the amount of bytes taken from `buf_in`, the amount of bytes written to `buf_out`,
+    /// and whether the entirety of the output has been written.
+    pub fn pack_to_mem(&mut self, buf_in: &[u8], buf_out: &mut [u8]) -> Result<(usize, usize, bool), Error> {
+        if self.output.is_none() {
+            if !buf_in.is_empty() {
+                self.input.extend_from_slice(buf_in);
+                return Ok((buf_in.len(), 0, false));
+            }
+
+            self.output = Some(self.serialise()?);
+        }
+
+        let output = self.output.as_ref().unwrap();
+        let written = (output.len() - self.output_pos).min(buf_out.len());
+        buf_out[..written].copy_from_slice(&output[self.output_pos..self.output_pos + written]);
+        self.output_pos += written;
+
+        Ok((0, written, self.output_pos == output.len()))
+    }
+
+    fn serialise(&mut self) -> Result<Vec<u8>, Error> {
+        let (body, is_base64) = pack::data_body(self.input.split_off(0), self.config.binary_policy)?;
+
+        if !self.want_headers {
+            return if is_base64 { Err(Error::UnknownFormat) } else { Ok(body.into_bytes()) };
+        }
+
+        let mut comment = None;
+        let body = if is_base64 {
+            set_comment_tag(&mut comment, BASE64_TAG.0, Some(BASE64_TAG.1));
+            body
+        } else {
+            let (body, crlf_lines) = pack::normalise_body(body, self.config.line_endings);
+            set_comment_tag(&mut comment, LINE_ENDINGS_TAG, crlf_lines.as_ref().map(|s| &s[..]));
+            body
+        };
+
+        let mut archive = HrxArchive::new(self.config.boundary_length);
+        archive.entries.insert(self.file_name.clone(),
+                               HrxEntry {
+                                   comment: comment,
+                                   data: HrxEntryData::File { body: Some(body) },
+                               });
+        pack::fit_boundary(&mut archive, &self.config);
+
+        let mut out = vec![];
+        archive.serialise(&mut out).map_err(|_| Error::Write)?;
+        Ok(out)
+    }
+}
@@ -1,160 +1,378 @@
 use linked_hash_map::Entry as LinkedHashMapEntry;
-use hrx::{HrxEntryData, HrxArchive, HrxEntry};
+use hrx::{HrxEntryData, HrxArchive, HrxEntry, HrxPath};
 use std::path::{PathBuf, Path};
-use std::num::NonZeroUsize;
-use libc::{c_int, INT_MAX};
-use std::convert::TryInto;
-use std::fs::{self, File};
-use self::super::state;
-use std::borrow::Cow;
-use std::io::Read;
-use std::ptr;
+use std::fs::{self, OpenOptions, File};
+use self::super::util::{minimal_boundary_length, system_time_to_rfc3339, normalise_line_endings, unescape_entry_path, host_to_entry_path, entry_to_host_path,
+                        archivable_path, wildcard_match, set_comment_tag, base64_encode};
+use self::super::config::{BinaryPolicy, LineEndings, SortOrder, Config};
+use self::super::progress::ProgressCallbacks;
+use self::super::error::Error;
+use self::super::state::{COMMENT_SIDECAR_SUFFIX, LINE_ENDINGS_TAG, BASE64_TAG, MTIME_TAG, set_sidecar_comment, listed_host_names};
+use std::io::{BufWriter, Write, Read};
+use std::ffi::OsString;
+use std::{iter, mem};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::io::ErrorKind as IoErrorKind;
+use std::process;
+
+
+/// Tells apart the temporary files of concurrent writes within this process
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+
+/// How [`pack_archive()`](fn.pack_archive.html) treats the files it packs, as specified by the host with the `PK_PACK_*` flags
+#[derive(Debug, Copy, Clone, Default, Hash, PartialEq, Eq)]
+pub struct PackFlags {
+    /// Delete the originals once the archive is written
+    pub move_files: bool,
+    /// Keep the paths of the files in `add_list` in their entry names, instead of only the file names
+    pub save_paths: bool,
+}
 
 
-pub fn pack_archive<Pf, Sup, Srp, Al, AlE>(packed_file: Pf, sub_path: Option<Sup>, source_path: Srp, add_list: Al, flags: c_int) -> Result<(), c_int>
+/// Pack the files in `add_list` into `packed_file`, returning the ones skipped as per `config.binary_policy`
+pub fn pack_archive<Pf, Sup, Srp, Al, AlE>(packed_file: Pf, sub_path: Option<Sup>, source_path: Srp, add_list: Al, flags: PackFlags, config: &Config,
+                                           progress: &ProgressCallbacks)
+                                           -> Result<Vec<String>, Error>
     where Pf: Into<PathBuf>,
           Sup: AsRef<str>,
           Srp: AsRef<Path>,
           Al: Iterator<Item = AlE>,
           AlE: AsRef<str>
 {
-    let (delete_originals, save_paths) = pack_archive_parse_flags(flags)?;
-
     let packed_file = packed_file.into();
-    let mut archive = pack_archive_load_archive(&packed_file)?;
+    let mut archive = pack_archive_load_archive(&packed_file, config)?;
 
-    let sub_path = sub_path.as_ref().map(AsRef::as_ref);
-    let source_path = source_path.as_ref();
-    for add_list_elem in add_list {
-        if pack_archive_add_element_to_archive(&mut archive, sub_path, source_path, add_list_elem.as_ref(), delete_originals, save_paths)? {
-            return Err(wcxhead::E_EABORTED);
+    let (skipped, packed_files) = pack_into_archive(&mut archive, sub_path, source_path, add_list, flags.save_paths, config, progress)?;
+
+    write_archive(&archive, &packed_file, config.backup)?;
+    // Only deleted once the archive is written, so nothing's lost if that never happens
+    if flags.move_files {
+        for moved_file in packed_files {
+            fs::remove_file(moved_file).map_err(|_| Error::Open)?;
         }
     }
+    progress.file_percent(&packed_file.to_string_lossy(), 100);
 
-    pack_archive_write_archive(archive, packed_file)
+    Ok(skipped)
 }
 
-fn pack_archive_parse_flags(flags: c_int) -> Result<(bool, bool), c_int> {
-    if (flags & wcxhead::PK_PACK_ENCRYPT) != 0 {
-        return Err(wcxhead::E_NOT_SUPPORTED);
+/// Pack the files in `add_list` into the specified archive, as [`pack_archive()`](fn.pack_archive.html) does before writing it
+///
+/// Returns the files skipped as per `config.binary_policy`, alongside the paths of the ones packed, for moving them.
+/// The archive is left partially updated on failure.
+pub fn pack_into_archive<Sup, Srp, Al, AlE>(archive: &mut HrxArchive, sub_path: Option<Sup>, source_path: Srp, add_list: Al, save_paths: bool,
+                                            config: &Config, progress: &ProgressCallbacks)
+                                            -> Result<(Vec<String>, Vec<PathBuf>), Error>
+    where Sup: AsRef<str>,
+          Srp: AsRef<Path>,
+          Al: Iterator<Item = AlE>,
+          AlE: AsRef<str>
+{
+    let sub_path = sub_path.as_ref().map(AsRef::as_ref);
+    let source_path = source_path.as_ref();
+
+    // Comment sidecars are applied last, so that the entries they describe are already in the archive
+    let (sidecars, add_list): (Vec<_>, Vec<_>) =
+        add_list.map(|e| e.as_ref().to_string()).partition(|e| config.comment_sidecars && e.ends_with(COMMENT_SIDECAR_SUFFIX));
+
+    let mut skipped = vec![];
+    let mut packed_files = vec![];
+    for add_list_elem in add_list.into_iter().chain(sidecars) {
+        let applied = if config.comment_sidecars && add_list_elem.ends_with(COMMENT_SIDECAR_SUFFIX) {
+            pack_archive_apply_comment_sidecar(archive, sub_path, source_path, &add_list_elem, save_paths, progress)
+        } else {
+            Ok(None)
+        };
+
+        match applied.and_then(|applied| match applied {
+            Some(aborted) => Ok(aborted),
+            None => pack_archive_add_element_to_archive(archive, sub_path, source_path, &add_list_elem, save_paths, config, progress),
+        }) {
+            Ok(true) => return Err(Error::Aborted),
+            Ok(false) => {
+                if !host_to_entry_path(&add_list_elem).ends_with('/') {
+                    packed_files.push(source_path.join(&add_list_elem));
+                }
+            }
+            Err(Error::UnknownFormat) if config.binary_policy == BinaryPolicy::Skip => skipped.push(add_list_elem),
+            Err(err) => return Err(err),
+        }
     }
 
-    Ok(((flags & wcxhead::PK_PACK_MOVE_FILES) != 0, (flags & wcxhead::PK_PACK_SAVE_PATHS) != 0))
+    // A new archive can end up empty, and nothing has no boundary to be recognised by
+    if archive.entries.is_empty() && archive.comment.is_none() {
+        return Err(Error::NoFiles);
+    }
+
+    sort_entries(archive, config.sort_order);
+    fit_boundary(archive, config);
+
+    Ok((skipped, packed_files))
 }
 
-fn pack_archive_load_archive(packed_file: &Path) -> Result<HrxArchive, c_int> {
+fn pack_archive_load_archive(packed_file: &Path, config: &Config) -> Result<HrxArchive, Error> {
     if !packed_file.exists() {
-        Ok(HrxArchive::new(NonZeroUsize::new(3).unwrap()))
+        Ok(HrxArchive::new(config.boundary_length))
     } else {
         load_archive(packed_file)
     }
 }
 
-fn pack_archive_add_element_to_archive(archive: &mut HrxArchive, sub_path: Option<&str>, source_path: &Path, add_list_elem: &str, delete_originals: bool,
-                                       save_paths: bool)
-                                       -> Result<bool, c_int> {
+fn pack_archive_add_element_to_archive(archive: &mut HrxArchive, sub_path: Option<&str>, source_path: &Path, add_list_elem: &str, save_paths: bool,
+                                       config: &Config, progress: &ProgressCallbacks)
+                                       -> Result<bool, Error> {
     let fs_path = source_path.join(add_list_elem);
 
-    let add_list_elem = if add_list_elem.contains('\\') {
-        Cow::from(add_list_elem.replace('\\', "/"))
-    } else {
-        Cow::from(add_list_elem)
-    };
+    let add_list_elem = host_to_entry_path(add_list_elem);
 
-    let (file_data_len, file_data, is_dir) = if add_list_elem.ends_with('/') {
-        (0, HrxEntryData::Directory, true)
+    let (file_data, is_dir, is_base64, crlf_lines) = if add_list_elem.ends_with('/') {
+        (HrxEntryData::Directory, true, false, None)
     } else {
-        let file_data = read_file_string(&fs_path)?;
-        (file_data.len(), HrxEntryData::File { body: Some(file_data) }, false)
+        let (file_data, is_base64) = read_file_body(&fs_path, config.binary_policy, progress)?;
+        let (file_data, crlf_lines) = if is_base64 {
+            (file_data, None)
+        } else {
+            normalise_body(file_data, config.line_endings)
+        };
+        (HrxEntryData::File { body: Some(file_data) }, false, is_base64, crlf_lines)
     };
 
     if is_dir && !save_paths {
         return Ok(false);
     }
 
-    let add_list_elem = if !save_paths {
-        match add_list_elem.rfind('/') {
-            Some(last_slash) => &add_list_elem[last_slash + 1..],
-            None => &add_list_elem[..],
-        }
+    let file_path = pack_archive_element_path(sub_path,
+                                              if is_dir {
+                                                  &add_list_elem[..add_list_elem.len() - 1]
+                                              } else {
+                                                  &add_list_elem[..]
+                                              },
+                                              save_paths)?;
+    if pack_archive_element_conflicts(archive, file_path.as_ref(), is_dir) {
+        return Err(Error::Create);
+    }
+    // Directories implied by the entries within them can't be listed after them
+    if is_dir && !archive.entries.contains_key(file_path.as_ref()) && pack_archive_element_has_children(archive, file_path.as_ref()) {
+        return Ok(progress.file_percent(&fs_path.to_string_lossy(), 100));
+    }
+
+    let mtime = if config.timestamps_in_comments && !is_dir {
+        fs::metadata(&fs_path).and_then(|m| m.modified()).ok().map(|t| system_time_to_rfc3339(&t))
     } else {
-        if is_dir {
-            &add_list_elem[..add_list_elem.len() - 1]
-        } else {
-            &add_list_elem[..]
-        }
+        None
     };
 
-    let file_path = match sub_path {
-            Some(sub_path) => format!("{}/{}", sub_path, add_list_elem).parse(),
-            None => add_list_elem.parse(),
-        }.map_err(|_| wcxhead::E_NOT_SUPPORTED)?;
-
-    match archive.entries.entry(file_path) {
-        LinkedHashMapEntry::Occupied(oe) => oe.into_mut().data = file_data,
+    let entry = match archive.entries.entry(file_path) {
+        LinkedHashMapEntry::Occupied(oe) => {
+            let entry = oe.into_mut();
+            entry.data = file_data;
+            entry
+        }
         LinkedHashMapEntry::Vacant(ve) => {
             ve.insert(HrxEntry {
                 comment: None,
                 data: file_data,
-            });
+            })
         }
-    }
+    };
+    set_comment_tag(&mut entry.comment,
+                    BASE64_TAG.0,
+                    if is_base64 {
+                        Some(BASE64_TAG.1)
+                    } else {
+                        None
+                    });
+    set_comment_tag(&mut entry.comment, MTIME_TAG, mtime.as_ref().map(|s| &s[..]));
+    set_comment_tag(&mut entry.comment, LINE_ENDINGS_TAG, crlf_lines.as_ref().map(|s| &s[..]));
+
+    // Files were reported as they were read
+    Ok(is_dir && progress.file_percent(&fs_path.to_string_lossy(), 100))
+}
 
-    if delete_originals && !is_dir {
-        fs::remove_file(fs_path).map_err(|_| wcxhead::E_EOPEN)?;
-    }
+/// Set the comment of the entry the specified `<name>.comment` file is a sidecar for
+///
+/// Returns `None` if there is no such entry, and whether the operation was aborted otherwise.
+fn pack_archive_apply_comment_sidecar(archive: &mut HrxArchive, sub_path: Option<&str>, source_path: &Path, add_list_elem: &str, save_paths: bool,
+                                      progress: &ProgressCallbacks)
+                                      -> Result<Option<bool>, Error> {
+    let fs_path = source_path.join(add_list_elem);
+
+    let add_list_elem = host_to_entry_path(add_list_elem);
+
+    let entry_path = pack_archive_element_path(sub_path, &add_list_elem[..add_list_elem.len() - COMMENT_SIDECAR_SUFFIX.len()], save_paths)?;
+    let entry = match archive.entries.get_mut(entry_path.as_ref()) {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+
+    let comment = read_file_string(&fs_path)?;
+    let comment_len = comment.len();
 
-    Ok(data_processed(file_data_len))
+    set_sidecar_comment(entry, Some(comment).filter(|c| !c.is_empty()));
+
+    Ok(Some(progress.data_processed(&fs_path.to_string_lossy(), comment_len)))
 }
 
-fn pack_archive_write_archive(mut archive: HrxArchive, packed_file: PathBuf) -> Result<(), c_int> {
-    if archive.validate_content().is_err() {
-        let mut boundlen = archive.boundary_length().get() + 1;
+/// Get the path in the archive of the specified `/`-separated add list element, without the trailing slash for directories
+fn pack_archive_element_path(sub_path: Option<&str>, add_list_elem: &str, save_paths: bool) -> Result<HrxPath, Error> {
+    let add_list_elem = if !save_paths {
+        match add_list_elem.rfind('/') {
+            Some(last_slash) => &add_list_elem[last_slash + 1..],
+            None => add_list_elem,
+        }
+    } else {
+        add_list_elem
+    };
 
-        while archive.set_boundary_length(NonZeroUsize::new(boundlen).unwrap()).is_err() {
-            boundlen += 1;
+    // Names are escaped when listed, so map them back to the paths they were listed for
+    match sub_path {
+            Some(sub_path) => archivable_path(&unescape_entry_path(&format!("{}/{}", sub_path, add_list_elem))),
+            None => archivable_path(&unescape_entry_path(add_list_elem)),
         }
-    }
+        .ok_or(Error::NotSupported)
+}
+
+/// Check if putting the specified entry into the archive would make a file the parent of another entry
+fn pack_archive_element_conflicts(archive: &HrxArchive, path: &str, is_dir: bool) -> bool {
+    let is_file = |p: &str| matches!(archive.entries.get(p), Some(HrxEntry { data: HrxEntryData::File { .. }, .. }));
+
+    path.match_indices('/').any(|(idx, _)| is_file(&path[..idx])) || (!is_dir && pack_archive_element_has_children(archive, path))
+}
+
+fn pack_archive_element_has_children(archive: &HrxArchive, path: &str) -> bool {
+    archive.entries.keys().any(|k| k.as_ref().strip_prefix(path).is_some_and(|rest| rest.starts_with('/')))
+}
 
-    write_archive(archive, packed_file)
+/// Set the boundary of the specified archive to the shortest one none of the bodies contain
+///
+/// The boundary can only get shorter than it was if `config.shrink_boundary`, and never shorter than `config.boundary_length`.
+pub fn fit_boundary(archive: &mut HrxArchive, config: &Config) {
+    let at_least = if config.shrink_boundary {
+        config.boundary_length
+    } else {
+        archive.boundary_length()
+    };
+
+    // Can't fail, since the new boundary is in none of the bodies
+    let _ = archive.set_boundary_length(minimal_boundary_length(archive, at_least));
+}
+
+/// Reorder the entries of the specified archive as requested
+///
+/// Directories sort before their contents either way, as the format requires.
+pub fn sort_entries(archive: &mut HrxArchive, order: SortOrder) {
+    match order {
+        SortOrder::Keep => {}
+        SortOrder::Path => {
+            let mut entries = mem::take(&mut archive.entries).into_iter().collect::<Vec<_>>();
+            entries.sort_by(|(lhs, _), (rhs, _)| lhs.as_ref().split('/').cmp(rhs.as_ref().split('/')));
+            archive.entries.extend(entries);
+        }
+    }
 }
 
 
-pub fn modify_archive<Pf, Dl, DlE>(packed_file: Pf, delete_list: Dl) -> Result<(), c_int>
+pub fn modify_archive<Pf, Dl, DlE>(packed_file: Pf, delete_list: Dl, config: &Config, progress: &ProgressCallbacks) -> Result<(), Error>
     where Pf: Into<PathBuf>,
           Dl: Iterator<Item = DlE>,
           DlE: AsRef<str>
 {
     let packed_file = packed_file.into();
+    let packed_file_name = packed_file.to_string_lossy().into_owned();
     let mut archive = load_archive(&packed_file)?;
 
-    for delete_list_elem in delete_list {
-        if modify_archive_delete_element_from_archive(&mut archive, delete_list_elem.as_ref())? {
-            return Err(wcxhead::E_EABORTED);
+    delete_from_archive(&mut archive, &packed_file_name, delete_list, config, progress)?;
+
+    // Emptied archives are removed, as the host does its own
+    if archive.entries.is_empty() && archive.comment.is_none() {
+        remove_archive(&packed_file, config.backup)?;
+    } else {
+        write_archive(&archive, &packed_file, config.backup)?;
+    }
+    progress.total_percent(&packed_file_name, 100);
+
+    Ok(())
+}
+
+/// Delete the entries in `delete_list` from the specified archive, as [`modify_archive()`](fn.modify_archive.html) does before
+/// writing it
+///
+/// The overall progress is reported under `archive_name`. The archive is left partially updated on failure.
+pub fn delete_from_archive<Dl, DlE>(archive: &mut HrxArchive, archive_name: &str, delete_list: Dl, config: &Config, progress: &ProgressCallbacks)
+                                    -> Result<(), Error>
+    where Dl: Iterator<Item = DlE>,
+          DlE: AsRef<str>
+{
+    // The host names the entries as they were listed, escaped
+    let listed = listed_host_names(archive, config.comment_sidecars);
+
+    // Deleted entries have no bytes to speak of, so this is reported in percentages, with the rewrite as one more step
+    let delete_list = delete_list.collect::<Vec<_>>();
+    let steps = delete_list.len() + 1;
+    for (idx, delete_list_elem) in delete_list.into_iter().enumerate() {
+        if modify_archive_delete_element_from_archive(archive, &listed, delete_list_elem.as_ref(), progress)? ||
+           progress.total_percent(archive_name, ((idx + 1) * 100 / steps) as u8) {
+            return Err(Error::Aborted);
         }
     }
 
-    write_archive(archive, packed_file)
+    sort_entries(archive, config.sort_order);
+    fit_boundary(archive, config);
+
+    Ok(())
 }
 
-fn modify_archive_delete_element_from_archive(archive: &mut HrxArchive, delete_list_elem: &str) -> Result<bool, c_int> {
-    let delete_list_elem = if delete_list_elem.contains('\\') {
-        Cow::from(delete_list_elem.replace('\\', "/"))
-    } else {
-        Cow::from(delete_list_elem)
-    };
+fn modify_archive_delete_element_from_archive(archive: &mut HrxArchive, listed: &[(HrxPath, String)], delete_list_elem: &str, progress: &ProgressCallbacks)
+                                              -> Result<bool, Error> {
+    let delete_list_elem = host_to_entry_path(delete_list_elem);
+    let delete_list_elem = delete_list_elem.trim_end_matches('/');
+
+    // Totalcmd deletes directories as "dir\*.*", which needs to take out the directory entry itself, too
+    let dir_itself = delete_list_elem.strip_suffix("/*.*").or_else(|| delete_list_elem.strip_suffix("/*"));
 
-    match archive.entries.remove(&delete_list_elem[..]) {
-        Some(entry) => {
-            let entry_data_len = match entry.data {
-                HrxEntryData::File { body } => body.map(|s| s.len()).unwrap_or(0),
-                HrxEntryData::Directory => 0,
-            };
+    // Matched against the listing from before anything was deleted, so earlier elements don't take later ones' matches away
+    let matching = listed.iter().filter(|(_, host_name)| modify_archive_element_matches(host_name, delete_list_elem, dir_itself)).collect::<Vec<_>>();
+    if matching.is_empty() {
+        return Err(Error::NoFiles);
+    }
 
-            Ok(data_processed(entry_data_len))
+    for (path, host_name) in matching {
+        // Deleting a comment sidecar takes the comment out of the entry it's for, unless that one's already gone
+        let removed = archive.entries.remove(path).is_some() ||
+                      match modify_archive_sidecar_entry(archive, path.as_ref()).and_then(|entry_path| archive.entries.get_mut(entry_path)) {
+            Some(entry) => {
+                set_sidecar_comment(entry, None);
+                true
+            }
+            None => false,
+        };
+        if removed && progress.file_percent(&entry_to_host_path(host_name), 100) {
+            return Ok(true);
         }
-        None => Err(wcxhead::E_NO_FILES),
     }
+
+    Ok(false)
+}
+
+/// Get the path of the entry the specified listed path is the virtual comment sidecar of, if it is one
+fn modify_archive_sidecar_entry<'p>(archive: &HrxArchive, path: &'p str) -> Option<&'p str> {
+    let entry_path = path.strip_suffix(COMMENT_SIDECAR_SUFFIX)?;
+
+    // Otherwise it's a directory implied by the entries within it
+    if archive.entries.contains_key(entry_path) && !pack_archive_element_has_children(archive, path) {
+        Some(entry_path)
+    } else {
+        None
+    }
+}
+
+/// Check if the specified host name or any of its parent directories match the pattern
+fn modify_archive_element_matches(path: &str, pattern: &str, dir_itself: Option<&str>) -> bool {
+    path.match_indices('/')
+        .map(|(idx, _)| &path[..idx])
+        .chain(iter::once(path))
+        .any(|p| dir_itself == Some(p) || wildcard_match(pattern, p))
 }
 
 
@@ -163,40 +381,144 @@ pub fn is_valid_archive<Fn: AsRef<Path>>(file_name: Fn) -> bool {
 }
 
 fn is_valid_archive_impl(file_name: &Path) -> bool {
-    load_archive(&file_name).is_ok()
+    load_archive(file_name).is_ok()
 }
 
 
-fn read_file_string(path: &Path) -> Result<String, c_int> {
-    let mut file = File::open(path).map_err(|_| wcxhead::E_EOPEN)?;
+fn read_file(path: &Path) -> Result<Vec<u8>, Error> {
+    let mut file = File::open(path).map_err(|_| Error::Open)?;
 
     let mut bytes = Vec::with_capacity(file.metadata().map(|m| m.len() as usize + 1 /* stolen from std::fs::read() */).unwrap_or(0));
-    file.read_to_end(&mut bytes).map_err(|_| wcxhead::E_EREAD)?;
+    file.read_to_end(&mut bytes).map_err(|_| Error::Read)?;
 
-    String::from_utf8(bytes).map_err(|_| wcxhead::E_UNKNOWN_FORMAT)
+    Ok(bytes)
 }
 
-fn load_archive(path: &Path) -> Result<HrxArchive, c_int> {
-    read_file_string(path)?.parse().map_err(|_| wcxhead::E_BAD_ARCHIVE)
+fn read_file_string(path: &Path) -> Result<String, Error> {
+    String::from_utf8(read_file(path)?).map_err(|_| Error::UnknownFormat)
 }
 
-fn write_archive(archive: HrxArchive, packed_file: PathBuf) -> Result<(), c_int> {
-    let mut out_f = File::create(packed_file).map_err(|_| wcxhead::E_ECREATE)?;
+/// Like [`read_file()`](fn.read_file.html), but reading, and reporting the progress of, `ProgressCallbacks::CHUNK_SIZE` bytes at a time
+fn read_file_reporting(path: &Path, progress: &ProgressCallbacks) -> Result<Vec<u8>, Error> {
+    let mut file = File::open(path).map_err(|_| Error::Open)?;
+    let name = path.to_string_lossy();
 
-    // Assume boundary was verified, so the only error can be I/O
-    archive.serialise(&mut out_f).map_err(|_| wcxhead::E_EWRITE)?;
+    let mut bytes = Vec::with_capacity(file.metadata().map(|m| m.len() as usize + 1 /* stolen from std::fs::read() */).unwrap_or(0));
+    loop {
+        let read = (&mut file).take(ProgressCallbacks::CHUNK_SIZE as u64).read_to_end(&mut bytes).map_err(|_| Error::Read)?;
+        if progress.data_processed(&name, read) {
+            return Err(Error::Aborted);
+        }
 
-    Ok(())
+        if read < ProgressCallbacks::CHUNK_SIZE {
+            return Ok(bytes);
+        }
+    }
+}
+
+/// Read the specified file into an entry body, returning whether it was base64-encoded
+fn read_file_body(path: &Path, binary_policy: BinaryPolicy, progress: &ProgressCallbacks) -> Result<(String, bool), Error> {
+    data_body(read_file_reporting(path, progress)?, binary_policy)
 }
 
-fn data_processed(len: usize) -> bool {
-    let len = len.try_into().unwrap_or(INT_MAX);
+/// Turn the specified data into an entry body as per `binary_policy` if it's not valid UTF-8, returning whether it was base64-encoded
+///
+/// Fails with `Error::UnknownFormat` if the data is to be skipped.
+pub fn data_body(data: Vec<u8>, binary_policy: BinaryPolicy) -> Result<(String, bool), Error> {
+    match String::from_utf8(data) {
+        Ok(body) => Ok((body, false)),
+        Err(err) => {
+            match binary_policy {
+                BinaryPolicy::Skip => Err(Error::UnknownFormat),
+                BinaryPolicy::Base64 => Ok((base64_encode(err.as_bytes()), true)),
+                BinaryPolicy::Transcode(encoding) => Ok((encoding.decode(err.as_bytes()), false)),
+            }
+        }
+    }
+}
+
+/// Convert the line endings of the specified text body as requested, alongside which lines ended in CRLF, if that needs recording
+pub fn normalise_body(body: String, line_endings: LineEndings) -> (String, Option<String>) {
+    if line_endings == LineEndings::Keep {
+        return (body, None);
+    }
+
+    match normalise_line_endings(&body) {
+        Some((normalised, crlf_lines)) => (normalised, Some(crlf_lines)),
+        None => (body, None),
+    }
+}
+
+fn load_archive(path: &Path) -> Result<HrxArchive, Error> {
+    read_file_string(path)?.parse().map_err(|_| Error::BadArchive)
+}
+
+/// Replace `packed_file` with the serialised archive, backing the original up to `packed_file.bak` if requested
+///
+/// The archive is written to a temporary sibling file first, so `packed_file` is left untouched on failure.
+pub fn write_archive(archive: &HrxArchive, packed_file: &Path, backup: bool) -> Result<(), Error> {
+    write_archive_with(archive, packed_file, backup, BufWriter::new)
+}
+
+/// Like [`write_archive()`](fn.write_archive.html), but serialising through the writer made from the temporary file
+pub fn write_archive_with<W: Write, F: FnOnce(File) -> W>(archive: &HrxArchive, packed_file: &Path, backup: bool, writer: F) -> Result<(), Error> {
+    let (temp_file, out_f) = create_temporary(packed_file)?;
+
+    let result = write_archive_temporary(archive, out_f, writer).and_then(|_| write_archive_replace(&temp_file, packed_file, backup));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_file);
+    }
+    result
+}
 
-    if let Some(cbk) = unsafe { state::GLOBAL_PROCESS_DATA_CALLBACK_W } {
-        cbk(ptr::null_mut(), len) == 0
-    } else if let Some(cbk) = unsafe { state::GLOBAL_PROCESS_DATA_CALLBACK } {
-        cbk(ptr::null_mut(), len) == 0
+/// Remove `packed_file`, moving it to `packed_file.bak` instead if requested
+fn remove_archive(packed_file: &Path, backup: bool) -> Result<(), Error> {
+    if backup {
+        let mut backup_file = packed_file.as_os_str().to_os_string();
+        backup_file.push(".bak");
+        fs::rename(packed_file, backup_file).map_err(|_| Error::Create)
     } else {
-        false
+        fs::remove_file(packed_file).map_err(|_| Error::Create)
     }
 }
+
+/// Create a new `.{packed_file}.{pid}.{n}.tmp` sibling, never reusing one that already exists
+fn create_temporary(packed_file: &Path) -> Result<(PathBuf, File), Error> {
+    let file_name = packed_file.file_name().ok_or(Error::Create)?;
+    loop {
+        let mut temp_name = OsString::from(".");
+        temp_name.push(file_name);
+        temp_name.push(format!(".{}.{}.tmp", process::id(), TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)));
+        let temp_file = packed_file.with_file_name(temp_name);
+
+        match OpenOptions::new().write(true).create_new(true).open(&temp_file) {
+            Ok(out_f) => return Ok((temp_file, out_f)),
+            Err(ref err) if err.kind() == IoErrorKind::AlreadyExists => {}
+            Err(_) => return Err(Error::Create),
+        }
+    }
+}
+
+fn write_archive_temporary<W: Write, F: FnOnce(File) -> W>(archive: &HrxArchive, out_f: File, writer: F) -> Result<(), Error> {
+    let mut out = writer(out_f.try_clone().map_err(|_| Error::Create)?);
+
+    // Assume boundary was verified, so the only error can be I/O
+    archive.serialise(&mut out).map_err(|_| Error::Write)?;
+    out.flush().map_err(|_| Error::Write)?;
+
+    out_f.sync_all().map_err(|_| Error::Write)
+}
+
+fn write_archive_replace(temp_file: &Path, packed_file: &Path, backup: bool) -> Result<(), Error> {
+    if let Ok(metadata) = fs::metadata(packed_file) {
+        fs::set_permissions(temp_file, metadata.permissions()).map_err(|_| Error::Create)?;
+
+        if backup {
+            let mut backup_file = packed_file.as_os_str().to_os_string();
+            backup_file.push(".bak");
+            fs::copy(packed_file, backup_file).map_err(|_| Error::Create)?;
+        }
+    }
+
+    fs::rename(temp_file, packed_file).map_err(|_| Error::Create)
+}
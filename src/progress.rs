@@ -0,0 +1,102 @@
+use self::super::util::string_to_ansi;
+use std::convert::TryInto;
+use std::cell::Cell;
+use std::ops::Deref;
+use libc::{c_char, c_int};
+use std::iter;
+
+
+/// `tProcessDataProc`, as called by the plugin
+pub type ProcessDataProc = extern "system" fn(FileName: *mut c_char, Size: c_int) -> c_int;
+
+/// `tProcessDataProcW`, as called by the plugin
+pub type ProcessDataProcW = extern "system" fn(FileName: *mut u16, Size: c_int) -> c_int;
+
+
+thread_local! {
+    /// Callbacks set for the next pack or delete operation, which the host starts from the same thread it set them from
+    static THREAD_CALLBACKS: Cell<ProgressCallbacks> = Cell::new(ProgressCallbacks::default());
+}
+
+
+/// Progress callbacks of a single operation, as set by [`SetProcessDataProc`](fn.SetProcessDataProc.html)
+#[derive(Clone, Copy, Default)]
+pub struct ProgressCallbacks {
+    pub ansi: Option<ProcessDataProc>,
+    pub wide: Option<ProcessDataProcW>,
+}
+
+impl ProgressCallbacks {
+    /// Amount of bytes read or written between progress reports
+    pub const CHUNK_SIZE: usize = 0x10000;
+
+    /// Get the callbacks set on the current thread for the next pack or delete operation
+    pub fn for_thread() -> ProgressCallbacks {
+        THREAD_CALLBACKS.with(Cell::get)
+    }
+
+    /// Set these callbacks for the next pack or delete operation started on the current thread
+    pub fn set_for_thread(self) {
+        THREAD_CALLBACKS.with(|c| c.set(self))
+    }
+
+    /// Get the callbacks set on the current thread for the pack or delete operation about to start, clearing them once it's done
+    ///
+    /// This keeps them from leaking into later operations on the same thread, which the host didn't set them for.
+    pub fn for_thread_operation() -> ThreadProgressCallbacks {
+        ThreadProgressCallbacks(ProgressCallbacks::for_thread())
+    }
+
+    /// Report `len` more bytes of the specified file as processed, returning whether the user aborted the operation
+    pub fn data_processed(&self, name: &str, len: usize) -> bool {
+        self.report(name, len.try_into().unwrap_or(c_int::MAX))
+    }
+
+    /// Report the specified file as `percent`% processed, for operations where byte counts aren't meaningful
+    ///
+    /// Returns whether the user aborted the operation.
+    pub fn file_percent(&self, name: &str, percent: u8) -> bool {
+        // -1..-100, as 0 would be taken for a byte count
+        self.report(name, -(percent.clamp(1, 100) as c_int))
+    }
+
+    /// Report the entire operation as `percent`% done, currently at the specified file
+    ///
+    /// Returns whether the user aborted the operation.
+    pub fn total_percent(&self, name: &str, percent: u8) -> bool {
+        // -1000..-1100
+        self.report(name, -1000 - percent.min(100) as c_int)
+    }
+
+    /// The wide callback is preferred if both are set
+    fn report(&self, name: &str, size: c_int) -> bool {
+        if let Some(cbk) = self.wide {
+            let mut name = name.encode_utf16().chain(iter::once(0)).collect::<Vec<_>>();
+            cbk(name.as_mut_ptr(), size) == 0
+        } else if let Some(cbk) = self.ansi {
+            let mut name = string_to_ansi(name).into_iter().filter(|&b| b != 0).chain(iter::once(0)).collect::<Vec<_>>();
+            cbk(name.as_mut_ptr() as *mut c_char, size) == 0
+        } else {
+            false
+        }
+    }
+}
+
+
+/// Callbacks taken from the current thread by [`ProgressCallbacks::for_thread_operation()`](struct.ProgressCallbacks.html#method.for_thread_operation),
+/// cleared from it when dropped
+pub struct ThreadProgressCallbacks(ProgressCallbacks);
+
+impl Deref for ThreadProgressCallbacks {
+    type Target = ProgressCallbacks;
+
+    fn deref(&self) -> &ProgressCallbacks {
+        &self.0
+    }
+}
+
+impl Drop for ThreadProgressCallbacks {
+    fn drop(&mut self) {
+        ProgressCallbacks::default().set_for_thread()
+    }
+}
@@ -0,0 +1,69 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{MutexGuard, Mutex, Arc};
+use std::collections::BTreeMap;
+
+
+/// Source of the handles of all registries, so that each handle is only ever valid for one of them, once
+static NEXT_HANDLE: AtomicUsize = AtomicUsize::new(1);
+
+
+/// Map of the opaque handles handed out to the host to the states they stand for
+///
+/// Handles are never reused, so ones already closed, belonging to a different registry, or made up altogether all simply aren't
+/// found, instead of being dereferenced.
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::HandleRegistry;
+/// let registry = HandleRegistry::new();
+/// let handle = registry.insert(String::from("state"));
+///
+/// assert_eq!(registry.with(handle, |s| s.len()), Some(5));
+/// assert_eq!(registry.remove(handle), true);
+///
+/// assert_eq!(registry.with(handle, |s| s.len()), None);
+/// assert_eq!(registry.remove(handle), false);
+/// ```
+pub struct HandleRegistry<T> {
+    states: Mutex<BTreeMap<usize, Arc<Mutex<T>>>>,
+}
+
+impl<T> Default for HandleRegistry<T> {
+    fn default() -> HandleRegistry<T> {
+        HandleRegistry::new()
+    }
+}
+
+impl<T> HandleRegistry<T> {
+    pub const fn new() -> HandleRegistry<T> {
+        HandleRegistry { states: Mutex::new(BTreeMap::new()) }
+    }
+
+    /// Register the specified state under a new handle
+    pub fn insert(&self, state: T) -> usize {
+        let handle = NEXT_HANDLE.fetch_add(1, Ordering::Relaxed);
+        self.states().insert(handle, Arc::new(Mutex::new(state)));
+        handle
+    }
+
+    /// Run the specified function on the state registered under the specified handle, if any
+    ///
+    /// Only calls on the same handle are serialised, and the registry itself isn't locked while the function runs.
+    pub fn with<R, F: FnOnce(&mut T) -> R>(&self, handle: usize, f: F) -> Option<R> {
+        let state = self.states().get(&handle).cloned()?;
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        Some(f(&mut state))
+    }
+
+    /// Unregister the specified handle, returning whether it was registered
+    ///
+    /// The state is dropped once the calls currently using it, if any, return.
+    pub fn remove(&self, handle: usize) -> bool {
+        self.states().remove(&handle).is_some()
+    }
+
+    fn states(&self) -> MutexGuard<'_, BTreeMap<usize, Arc<Mutex<T>>>> {
+        self.states.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
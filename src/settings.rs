@@ -0,0 +1,513 @@
+use self::super::config::{LegacyEncoding, BinaryPolicy, LineEndings, SortOrder, Config};
+use self::super::guard::set_panic_log;
+use self::super::error::Error;
+use std::path::{PathBuf, Path};
+use std::io::ErrorKind;
+use std::sync::Mutex;
+use std::fs;
+
+
+/// Config the exports operate with, once settings were applied
+static CURRENT_CONFIG: Mutex<Option<Config>> = Mutex::new(None);
+
+
+/// Plugin settings, as kept in the `[totalcmd-hrx]` section of the ini file suggested by the host
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::{BinaryPolicy, Settings};
+/// let settings = Settings::parse("[totalcmd-hrx]\nBinaryPolicy=base64\nBoundaryLength=5\n");
+/// assert_eq!(settings.config.binary_policy, BinaryPolicy::Base64);
+/// assert_eq!(settings.config.boundary_length.get(), 5);
+///
+/// assert_eq!(Settings::parse(&settings.write_into("")), settings);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Settings {
+    /// What the packing and unpacking operations run with
+    pub config: Config,
+    /// File to append caught panics to, relative to the directory of the ini file
+    pub panic_log: Option<PathBuf>,
+}
+
+impl Settings {
+    /// Name of the ini section the settings are kept in
+    pub const SECTION: &'static str = "totalcmd-hrx";
+
+    /// Read the settings from the section in the specified ini file contents
+    ///
+    /// Settings missing or set to something invalid are left at their defaults.
+    pub fn parse(ini: &str) -> Settings {
+        let mut settings = Settings::default();
+        for (key, value) in section_lines(ini).filter_map(key_value) {
+            settings.set_value(key, value);
+        }
+        settings
+    }
+
+    /// Get the specified ini file contents with the section updated to these settings, or appended if there was none
+    ///
+    /// Everything else is kept as it was, including comments, unknown keys in the section, and line endings; new lines end like
+    /// the rest of the file.
+    pub fn write_into(&self, ini: &str) -> String {
+        let newline = if ini.contains("\r\n") || (ini.is_empty() && cfg!(windows)) {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        let mut pending = self.lines();
+
+        // Each line alongside what it was terminated with
+        let mut lines = vec![];
+        // Past the last non-blank line of the section, where keys that weren't there yet go
+        let mut section_end = None;
+        let mut in_section = false;
+        for raw in ini.split_inclusive('\n') {
+            let line = raw.strip_suffix('\n').map(|l| l.strip_suffix('\r').unwrap_or(l)).unwrap_or(raw);
+            let terminator = &raw[line.len()..];
+
+            if let Some(name) = section_name(line) {
+                in_section = name.eq_ignore_ascii_case(Settings::SECTION);
+                if in_section {
+                    section_end = Some(lines.len() + 1);
+                }
+            } else if in_section {
+                if let Some((key, _)) = key_value(line) {
+                    if let Some(idx) = Settings::FIELDS.iter().position(|f| f.key.eq_ignore_ascii_case(key)) {
+                        // Repeats of keys already written are dropped
+                        if let Some(line) = pending[idx].take() {
+                            lines.push((line, terminator));
+                            section_end = Some(lines.len());
+                        }
+                        continue;
+                    }
+                }
+                if !line.trim().is_empty() {
+                    section_end = Some(lines.len() + 1);
+                }
+            }
+            lines.push((line.to_string(), terminator));
+        }
+
+        let pending = pending.into_iter().flatten().map(|line| (line, newline));
+        match section_end {
+            Some(section_end) => {
+                lines.splice(section_end..section_end, pending);
+            }
+            None => {
+                if lines.last().map(|(l, _)| !l.trim().is_empty()).unwrap_or(false) {
+                    lines.push((String::new(), newline));
+                }
+                lines.push((format!("[{}]", Settings::SECTION), newline));
+                lines.extend(pending);
+            }
+        }
+
+        let mut out = String::with_capacity(ini.len());
+        for (idx, (line, terminator)) in lines.iter().enumerate() {
+            out.push_str(line);
+            // Only the last line can be unterminated
+            out.push_str(if terminator.is_empty() && idx != lines.len() - 1 {
+                newline
+            } else {
+                terminator
+            });
+        }
+        out
+    }
+
+    /// Read the settings from the specified ini file, with the defaults for the ones it doesn't have
+    pub fn load<P: AsRef<Path>>(ini_path: P) -> Result<Settings, Error> {
+        let (ini, _) = read_ini(ini_path.as_ref())?.ok_or(Error::Open)?;
+        Ok(Settings::parse(&ini))
+    }
+
+    /// Read the settings from the specified ini file, first writing the default ones into it if it has none, for users to find
+    ///
+    /// Defaults are returned if the file can't be read.
+    pub fn load_or_init<P: AsRef<Path>>(ini_path: P) -> Settings {
+        Settings::load_or_init_impl(ini_path.as_ref())
+    }
+
+    fn load_or_init_impl(ini_path: &Path) -> Settings {
+        let (ini, encoding) = match read_ini(ini_path) {
+            Ok(Some(ini)) => ini,
+            Ok(None) => (String::new(), IniEncoding::Utf8),
+            Err(_) => return Settings::default(),
+        };
+
+        if ini.lines().filter_map(section_name).any(|n| n.eq_ignore_ascii_case(Settings::SECTION)) {
+            Settings::parse(&ini)
+        } else {
+            let settings = Settings::default();
+            let _ = write_ini(ini_path, &settings.write_into(&ini), encoding);
+            settings
+        }
+    }
+
+    /// Write these settings into the specified ini file, creating it if needed
+    pub fn save<P: AsRef<Path>>(&self, ini_path: P) -> Result<(), Error> {
+        self.save_impl(ini_path.as_ref())
+    }
+
+    fn save_impl(&self, ini_path: &Path) -> Result<(), Error> {
+        let (ini, encoding) = read_ini(ini_path)?.unwrap_or((String::new(), IniEncoding::Utf8));
+        write_ini(ini_path, &self.write_into(&ini), encoding)
+    }
+
+    /// Make these the settings the exports operate with, with the panic log relative to `ini_dir`
+    pub fn apply(&self, ini_dir: &Path) {
+        *CURRENT_CONFIG.lock().unwrap_or_else(|e| e.into_inner()) = Some(self.config);
+        set_panic_log(self.panic_log.as_ref().map(|p| ini_dir.join(p)));
+    }
+
+
+    /// Every setting, in the order they're written and shown in
+    pub const FIELDS: [SettingField; 9] = [SettingField {
+                                               key: "BoundaryLength",
+                                               label: "Boundary length",
+                                               kind: SettingKind::Number,
+                                           },
+                                           SettingField {
+                                               key: "ShrinkBoundary",
+                                               label: "Shrink the boundary when possible",
+                                               kind: SettingKind::Flag,
+                                           },
+                                           SettingField {
+                                               key: "BinaryPolicy",
+                                               label: "Binary files",
+                                               kind: SettingKind::Choice(&["skip", "base64", "latin1", "windows-1252"]),
+                                           },
+                                           SettingField {
+                                               key: "Backup",
+                                               label: "Back up archives before modifying them",
+                                               kind: SettingKind::Flag,
+                                           },
+                                           SettingField {
+                                               key: "TimestampsInComments",
+                                               label: "Keep timestamps in entry comments",
+                                               kind: SettingKind::Flag,
+                                           },
+                                           SettingField {
+                                               key: "CommentSidecars",
+                                               label: "List entry comments as sidecar files",
+                                               kind: SettingKind::Flag,
+                                           },
+                                           SettingField {
+                                               key: "SortOrder",
+                                               label: "Entry order",
+                                               kind: SettingKind::Choice(&["keep", "path"]),
+                                           },
+                                           SettingField {
+                                               key: "LineEndings",
+                                               label: "Line endings",
+                                               kind: SettingKind::Choice(&["keep", "lf", "crlf"]),
+                                           },
+                                           SettingField {
+                                               key: "PanicLog",
+                                               label: "Panic log",
+                                               kind: SettingKind::Text,
+                                           }];
+
+    /// Get the value of the setting under the specified key, as it'd be written to the ini file
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use totalcmd_hrx::Settings;
+    /// let settings = Settings::default();
+    /// assert_eq!(settings.value("BoundaryLength"), Some("3".to_string()));
+    /// assert_eq!(settings.value("Backup"), Some("0".to_string()));
+    /// assert_eq!(settings.value("Unknown"), None);
+    /// ```
+    pub fn value(&self, key: &str) -> Option<String> {
+        let config = &self.config;
+        Some(match &key.to_ascii_lowercase()[..] {
+                "boundarylength" => config.boundary_length.to_string(),
+                "shrinkboundary" => bool_value(config.shrink_boundary).to_string(),
+                "binarypolicy" => binary_policy_value(config.binary_policy).to_string(),
+                "backup" => bool_value(config.backup).to_string(),
+                "timestampsincomments" => bool_value(config.timestamps_in_comments).to_string(),
+                "commentsidecars" => bool_value(config.comment_sidecars).to_string(),
+                "sortorder" => sort_order_value(config.sort_order).to_string(),
+                "lineendings" => line_endings_value(config.line_endings).to_string(),
+                "paniclog" => self.panic_log.as_ref().map(|p| p.display().to_string()).unwrap_or_default(),
+                _ => return None,
+            })
+    }
+
+    /// Set the setting under the specified key to the specified value, as read from the ini file
+    ///
+    /// Returns whether it was, i.e. whether the key is known and the value valid for it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use totalcmd_hrx::Settings;
+    /// let mut settings = Settings::default();
+    /// assert!(settings.set_value("BoundaryLength", "5"));
+    /// assert!(!settings.set_value("BoundaryLength", "0"));
+    /// assert_eq!(settings.config.boundary_length.get(), 5);
+    /// ```
+    pub fn set_value(&mut self, key: &str, value: &str) -> bool {
+        let config = &mut self.config;
+        match &key.to_ascii_lowercase()[..] {
+            "boundarylength" => value.parse().map(|l| config.boundary_length = l).is_ok(),
+            "shrinkboundary" => parse_bool(value).map(|b| config.shrink_boundary = b).is_some(),
+            "binarypolicy" => parse_binary_policy(value).map(|p| config.binary_policy = p).is_some(),
+            "backup" => parse_bool(value).map(|b| config.backup = b).is_some(),
+            "timestampsincomments" => parse_bool(value).map(|b| config.timestamps_in_comments = b).is_some(),
+            "commentsidecars" => parse_bool(value).map(|b| config.comment_sidecars = b).is_some(),
+            "sortorder" => parse_sort_order(value).map(|o| config.sort_order = o).is_some(),
+            "lineendings" => parse_line_endings(value).map(|e| config.line_endings = e).is_some(),
+            "paniclog" => {
+                self.panic_log = if value.is_empty() {
+                    None
+                } else {
+                    Some(PathBuf::from(value))
+                };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Let `edit` change the settings in the specified ini file, and save them there unless it returns `false`
+    ///
+    /// This is what the options dialog runs in, returning the saved settings, or `None` if cancelled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use totalcmd_hrx::Settings;
+    /// # use std::{env, fs};
+    /// let ini = env::temp_dir().join("totalcmd-hrx-doctest-edit.ini");
+    /// # let _ = fs::remove_file(&ini);
+    /// assert_eq!(Settings::edit(&ini, |s| s.set_value("SortOrder", "path")).unwrap().unwrap().value("SortOrder"),
+    ///            Some("path".to_string()));
+    /// assert_eq!(Settings::edit(&ini, |s| !s.set_value("SortOrder", "keep")), Ok(None));
+    /// assert_eq!(Settings::load(&ini).unwrap().value("SortOrder"), Some("path".to_string()));
+    /// ```
+    pub fn edit<P: AsRef<Path>, F: FnOnce(&mut Settings) -> bool>(ini_path: P, edit: F) -> Result<Option<Settings>, Error> {
+        Settings::edit_impl(ini_path.as_ref(), edit)
+    }
+
+    fn edit_impl<F: FnOnce(&mut Settings) -> bool>(ini_path: &Path, edit: F) -> Result<Option<Settings>, Error> {
+        let mut settings = read_ini(ini_path)?.map(|(ini, _)| Settings::parse(&ini)).unwrap_or_default();
+        if !edit(&mut settings) {
+            return Ok(None);
+        }
+
+        settings.save_impl(ini_path)?;
+        Ok(Some(settings))
+    }
+
+
+    /// The `key=value` lines of every setting, in the order of `FIELDS`
+    fn lines(&self) -> Vec<Option<String>> {
+        Settings::FIELDS.iter().map(|f| self.value(f.key).map(|v| format!("{}={}", f.key, v))).collect()
+    }
+}
+
+
+/// A setting, as presented in the options dialog
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SettingField {
+    /// What it's kept under in the ini file
+    pub key: &'static str,
+    /// What it's described as to the user
+    pub label: &'static str,
+    /// What values it takes
+    pub kind: SettingKind,
+}
+
+/// What values a setting takes, and so how it's edited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingKind {
+    /// `1` or `0`, with a checkbox
+    Flag,
+    /// A positive integer
+    Number,
+    /// One of the specified values, picked from a list
+    Choice(&'static [&'static str]),
+    /// Any text, including none
+    Text,
+}
+
+
+/// Get the config the exports operate with, as last [applied](struct.Settings.html#method.apply), or the default one
+pub fn current_config() -> Config {
+    CURRENT_CONFIG.lock().unwrap_or_else(|e| e.into_inner()).unwrap_or_default()
+}
+
+
+/// How an ini file was encoded, so it can be written back the same way
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IniEncoding {
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+}
+
+/// Read the specified ini file, or `None` if there is none
+///
+/// Unless the file is UTF-16, only the settings section is decoded as UTF-8, see [`decode_ini()`](fn.decode_ini.html).
+fn read_ini(path: &Path) -> Result<Option<(String, IniEncoding)>, Error> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(_) => return Err(Error::Read),
+    };
+
+    Ok(Some(if let Some(bytes) = bytes.strip_prefix(b"\xFF\xFE") {
+        (String::from_utf16_lossy(&bytes.chunks(2).map(|c| u16::from_le_bytes([c[0], *c.get(1).unwrap_or(&0)])).collect::<Vec<_>>()), IniEncoding::Utf16Le)
+    } else if let Some(bytes) = bytes.strip_prefix(b"\xEF\xBB\xBF") {
+        (decode_ini(bytes), IniEncoding::Utf8Bom)
+    } else {
+        (decode_ini(&bytes), IniEncoding::Utf8)
+    }))
+}
+
+fn write_ini(path: &Path, ini: &str, encoding: IniEncoding) -> Result<(), Error> {
+    let bytes = match encoding {
+        IniEncoding::Utf8 => encode_ini(ini),
+        IniEncoding::Utf8Bom => b"\xEF\xBB\xBF".iter().cloned().chain(encode_ini(ini)).collect(),
+        IniEncoding::Utf16Le => b"\xFF\xFE".iter().cloned().chain(ini.encode_utf16().flat_map(u16::to_le_bytes)).collect(),
+    };
+
+    fs::write(path, bytes).map_err(|_| Error::Write)
+}
+
+/// Decode the specified 8-bit ini file contents, with the settings section as UTF-8, and every other line byte-for-byte
+///
+/// The ini file is shared with the host and other plugins, whose sections can be in any encoding, usually the ANSI code page,
+/// and [`encode_ini()`](fn.encode_ini.html) writes them back exactly as they were.
+fn decode_ini(bytes: &[u8]) -> String {
+    let mut in_section = false;
+    bytes.split_inclusive(|&b| b == b'\n')
+        .map(|line| {
+            let raw = line.iter().map(|&b| b as char).collect::<String>();
+            if let Some(name) = section_name(&raw) {
+                in_section = name.eq_ignore_ascii_case(Settings::SECTION);
+            }
+
+            if in_section {
+                String::from_utf8_lossy(line).into_owned()
+            } else {
+                raw
+            }
+        })
+        .collect()
+}
+
+/// Reverse [`decode_ini()`](fn.decode_ini.html)
+fn encode_ini(ini: &str) -> Vec<u8> {
+    let mut in_section = false;
+    let mut ret = Vec::with_capacity(ini.len());
+    for line in ini.split_inclusive('\n') {
+        if let Some(name) = section_name(line) {
+            in_section = name.eq_ignore_ascii_case(Settings::SECTION);
+        }
+
+        if in_section {
+            ret.extend(line.as_bytes());
+        } else {
+            ret.extend(line.chars().map(|c| c as u8));
+        }
+    }
+    ret
+}
+
+/// The lines in the settings section of the specified ini file contents
+fn section_lines(ini: &str) -> impl Iterator<Item = &str> {
+    ini.lines()
+        .scan(false, |in_section, line| {
+            Some(match section_name(line) {
+                Some(name) => {
+                    *in_section = name.eq_ignore_ascii_case(Settings::SECTION);
+                    None
+                }
+                None if *in_section => Some(line),
+                None => None,
+            })
+        })
+        .flatten()
+}
+
+fn section_name(line: &str) -> Option<&str> {
+    line.trim().strip_prefix('[').and_then(|l| l.strip_suffix(']')).map(str::trim)
+}
+
+/// Split the specified `key=value` line, unless it's a comment
+fn key_value(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    if line.starts_with(';') || line.starts_with('#') {
+        return None;
+    }
+
+    line.split_once('=').map(|(k, v)| (k.trim(), v.trim())).filter(|(k, _)| !k.is_empty())
+}
+
+fn bool_value(b: bool) -> &'static str {
+    if b { "1" } else { "0" }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match &value.to_ascii_lowercase()[..] {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+fn binary_policy_value(policy: BinaryPolicy) -> &'static str {
+    match policy {
+        BinaryPolicy::Skip => "skip",
+        BinaryPolicy::Base64 => "base64",
+        BinaryPolicy::Transcode(LegacyEncoding::Latin1) => "latin1",
+        BinaryPolicy::Transcode(LegacyEncoding::Windows1252) => "windows-1252",
+    }
+}
+
+fn parse_binary_policy(value: &str) -> Option<BinaryPolicy> {
+    match &value.to_ascii_lowercase()[..] {
+        "skip" => Some(BinaryPolicy::Skip),
+        "base64" => Some(BinaryPolicy::Base64),
+        "latin1" => Some(BinaryPolicy::Transcode(LegacyEncoding::Latin1)),
+        "windows-1252" => Some(BinaryPolicy::Transcode(LegacyEncoding::Windows1252)),
+        _ => None,
+    }
+}
+
+fn sort_order_value(order: SortOrder) -> &'static str {
+    match order {
+        SortOrder::Keep => "keep",
+        SortOrder::Path => "path",
+    }
+}
+
+fn parse_sort_order(value: &str) -> Option<SortOrder> {
+    match &value.to_ascii_lowercase()[..] {
+        "keep" => Some(SortOrder::Keep),
+        "path" => Some(SortOrder::Path),
+        _ => None,
+    }
+}
+
+fn line_endings_value(line_endings: LineEndings) -> &'static str {
+    match line_endings {
+        LineEndings::Keep => "keep",
+        LineEndings::Lf => "lf",
+        LineEndings::Crlf => "crlf",
+    }
+}
+
+fn parse_line_endings(value: &str) -> Option<LineEndings> {
+    match &value.to_ascii_lowercase()[..] {
+        "keep" => Some(LineEndings::Keep),
+        "lf" => Some(LineEndings::Lf),
+        "crlf" => Some(LineEndings::Crlf),
+        _ => None,
+    }
+}
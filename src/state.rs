@@ -1,94 +1,525 @@
-use hrx::{HrxEntryData, HrxArchive, HrxEntry, HrxPath};
-use linked_hash_map::Iter as LinkedHashMapIter;
-use std::io::{Write, Read};
+use hrx::{ErroneousBodyPath, HrxEntryData, HrxArchive, HrxEntry, HrxError, HrxPath};
+use self::super::util::{rfc3339_to_system_time, restore_line_endings, sanitise_entry_path, crlf_line_endings, escape_entry_name, suffix_entry_name,
+                        set_comment_tag, base64_decode, crc32_update, comment_tag, crc32};
+use self::super::progress::ProgressCallbacks;
+use self::super::config::{LineEndings, Config};
+use self::super::error::Error;
+use hrx::util::boundary_str;
+use std::io::{self, Write, Read};
+use std::collections::{HashMap, HashSet};
 use std::time::SystemTime;
 use std::borrow::Cow;
-use std::path::Path;
-use std::fs::File;
-use libc::c_int;
-use std::ptr;
+use std::cell::Cell;
+use std::path::{Component, PathBuf, Path};
+use std::fs::{self, File};
+use std::{iter, mem};
 
 
-pub static mut GLOBAL_PROCESS_DATA_CALLBACK: Option<wcxhead::tProcessDataProc> = None;
-pub static mut GLOBAL_PROCESS_DATA_CALLBACK_W: Option<wcxhead::tProcessDataProcW> = None;
+/// Entry comment line marking the body as base64-encoded binary data
+pub const BASE64_TAG: (&str, &str) = ("encoding", "base64");
+
+/// Entry comment key for the RFC 3339 modification time of the packed file
+pub const MTIME_TAG: &str = "mtime";
+
+/// Entry comment key for the lines of the packed file that ended in CRLF, as described by
+/// [`normalise_line_endings()`](util/fn.normalise_line_endings.html)
+pub const LINE_ENDINGS_TAG: &str = "line-endings";
+
+/// Entry comment keys managed by the plugin itself, hidden from comment sidecars
+pub const MANAGED_TAGS: &[&str] = &[BASE64_TAG.0, MTIME_TAG, LINE_ENDINGS_TAG];
+
+/// Suffix of the virtual entries exposing the comments of the entries they're named after
+pub const COMMENT_SIDECAR_SUFFIX: &str = ".comment";
 
 
 pub struct ArchiveState {
     pub arch: HrxArchive,
+    pub raw: String,
+    /// Entries listed without being in `arch`, alongside the index of the entry in `arch` they precede
+    pub virtual_entries: Vec<(usize, HrxPath, HrxEntry)>,
     pub mod_time: SystemTime,
+    pub config: Config,
 
-    pub process_data_callback: Option<wcxhead::tProcessDataProc>,
-    pub process_data_callback_w: Option<wcxhead::tProcessDataProcW>,
+    pub progress: ProgressCallbacks,
 
-    arch_iter: Option<LinkedHashMapIter<'static, HrxPath, HrxEntry>>,
-    cur_entry: Option<(&'static HrxPath, &'static HrxEntry)>,
+    listing: Vec<ListedEntry>,
+    /// Index of the entry after the current one in `listing`
+    cursor: usize,
+    invalid_bodies: Option<Vec<ErroneousBodyPath>>,
+}
+
+/// An entry, real or virtual, in the order it's listed to the host in
+struct ListedEntry {
+    path: HrxPath,
+    /// Index in `ArchiveState::virtual_entries`, if not in `ArchiveState::arch`
+    virtual_idx: Option<usize>,
+    host_name: String,
+    /// CRC-32 and length of the extracted data, once computed
+    extracted: Cell<Option<(u32, u64)>>,
 }
 
 impl ArchiveState {
-    pub fn open<P: AsRef<Path>>(path: P) -> Result<ArchiveState, c_int> {
-        ArchiveState::open_impl(path.as_ref())
+    pub fn open<P: AsRef<Path>>(path: P, config: &Config) -> Result<ArchiveState, Error> {
+        ArchiveState::open_impl(path.as_ref(), config)
     }
 
-    fn open_impl(path: &Path) -> Result<ArchiveState, c_int> {
-        let mut file = File::open(path).map_err(|_| wcxhead::E_EOPEN)?;
+    fn open_impl(path: &Path, config: &Config) -> Result<ArchiveState, Error> {
+        let mut file = File::open(path).map_err(|_| Error::Open)?;
         let (file_len, file_time) = match file.metadata() {
             Ok(metadata) => (metadata.len() as usize + 1 /* stolen from std::fs::read() */, metadata.modified().ok().unwrap_or_else(SystemTime::now)),
             Err(_) => (0, SystemTime::now()),
         };
 
         let mut bytes = Vec::with_capacity(file_len);
-        file.read_to_end(&mut bytes).map_err(|_| wcxhead::E_EREAD)?;
+        file.read_to_end(&mut bytes).map_err(|_| Error::Read)?;
+
+        ArchiveState::from_bytes(bytes, file_time, config)
+    }
 
-        let string = String::from_utf8(bytes).map_err(|_| wcxhead::E_UNKNOWN_FORMAT)?;
+    /// Parse an archive already in memory, with entries without a recorded modification time last modified at `mod_time`
+    pub fn from_bytes(bytes: Vec<u8>, mod_time: SystemTime, config: &Config) -> Result<ArchiveState, Error> {
+        let string = String::from_utf8(bytes).map_err(|_| Error::UnknownFormat)?;
 
+        let arch = string.parse().map_err(|_| Error::BadArchive)?;
+        let virtual_entries = virtual_entries(&arch, config.comment_sidecars);
         Ok(ArchiveState {
-            arch: string.parse().map_err(|_| wcxhead::E_BAD_ARCHIVE)?,
-            mod_time: file_time,
-            process_data_callback: unsafe { GLOBAL_PROCESS_DATA_CALLBACK },
-            process_data_callback_w: unsafe { GLOBAL_PROCESS_DATA_CALLBACK_W },
-            arch_iter: None,
-            cur_entry: None,
+            listing: listing(&arch, &virtual_entries),
+            virtual_entries: virtual_entries,
+            arch: arch,
+            raw: string,
+            mod_time: mod_time,
+            config: *config,
+            progress: ProgressCallbacks::for_thread(),
+            cursor: 0,
+            invalid_bodies: None,
         })
     }
 
-    pub fn next_entry(&'static mut self) -> Option<(&'static HrxPath, &'static HrxEntry)> {
-        if self.arch_iter.is_none() {
-            self.arch_iter = Some(self.arch.entries.iter());
+    /// Advance to the next listed entry, returning its path, the entry itself, and the name it's presented to the host as
+    pub fn next_entry(&mut self) -> Option<(&HrxPath, &HrxEntry, &str)> {
+        if self.cursor <= self.listing.len() {
+            self.cursor += 1;
         }
 
-        self.cur_entry = self.arch_iter.as_mut().unwrap().next();
-        self.cur_entry
+        self.current_entry()
     }
 
-    pub fn extract_current_entry<Pd: AsRef<Path>, Pn: AsRef<Path>>(&self, dest_path: Option<Pd>, dest_name: Option<Pn>) -> Result<(), c_int> {
-        self.extract_current_entry_impl(dest_path.as_ref().map(AsRef::as_ref), dest_name.as_ref().map(AsRef::as_ref))
+    /// Get the path, the entry itself, and the host name of the entry last returned by `next_entry()`
+    pub fn current_entry(&self) -> Option<(&HrxPath, &HrxEntry, &str)> {
+        let listed = self.listing.get(self.cursor.checked_sub(1)?)?;
+        let entry = match listed.virtual_idx {
+            Some(idx) => &self.virtual_entries[idx].2,
+            None => self.arch.entries.get(&listed.path)?,
+        };
+
+        Some((&listed.path, entry, &listed.host_name))
+    }
+
+    /// Get the CRC-32 of the data the current entry extracts to, computing it the first time it's asked for
+    ///
+    /// Directories have no data, and so a CRC of 0.
+    pub fn current_crc(&self) -> Result<u32, Error> {
+        self.current_extracted().map(|(crc, _)| crc)
+    }
+
+    /// Get the length of the data the current entry extracts to, which differs from that of its body if it's encoded
+    pub fn current_size(&self) -> Result<u64, Error> {
+        self.current_extracted().map(|(_, size)| size)
     }
 
-    fn extract_current_entry_impl(&self, dest_path: Option<&Path>, dest_name: Option<&Path>) -> Result<(), c_int> {
-        let data = match &self.cur_entry.ok_or(wcxhead::E_END_ARCHIVE)?.1.data {
-            HrxEntryData::File { body } => body.as_ref().map(|s| &s[..]).unwrap_or(""),
-            HrxEntryData::Directory => "",
+    fn current_extracted(&self) -> Result<(u32, u64), Error> {
+        let listed = self.cursor.checked_sub(1).and_then(|idx| self.listing.get(idx)).ok_or(Error::EndArchive)?;
+        if let Some(extracted) = listed.extracted.get() {
+            return Ok(extracted);
+        }
+
+        let (_, entry, _) = self.current_entry().ok_or(Error::EndArchive)?;
+        let extracted = entry_data(entry, self.config.line_endings)?.map(|data| (crc32(&data), data.len() as u64)).unwrap_or((0, 0));
+        listed.extracted.set(Some(extracted));
+        Ok(extracted)
+    }
+
+    /// Verify the current entry against the original bytes of the archive
+    ///
+    /// On failure, returns the error alongside the name the offending entry was listed as.
+    pub fn test_current_entry(&mut self) -> Result<(), (Error, String)> {
+        if self.invalid_bodies.is_none() {
+            self.invalid_bodies = Some(match self.arch.validate_content() {
+                Err(HrxError::BodyContainsBoundary(paths)) => paths,
+                _ => vec![],
+            });
+        }
+
+        let (fname, entry, host_name) = self.current_entry().ok_or((Error::EndArchive, String::new()))?;
+        let err = |code| (code, host_name.to_string());
+
+        if fname.as_ref().parse::<HrxPath>().ok().as_ref() != Some(fname) {
+            return Err(err(Error::BadArchive));
+        }
+        if sanitise_entry_path(host_name) != host_name {
+            return Err(err(Error::UnsafePath));
+        }
+
+        if !self.arch.entries.contains_key(fname.as_ref()) {
+            // Synthesised entry, not present in the original bytes
+            return Ok(());
+        }
+
+        if self.invalid_bodies.as_ref().unwrap().iter().any(|p| match p {
+            ErroneousBodyPath::EntryComment(p) |
+            ErroneousBodyPath::EntryData(p) => p == fname.as_ref(),
+            ErroneousBodyPath::RootComment => false,
+        }) {
+            return Err(err(Error::BadData));
+        }
+
+        let bound = boundary_str(self.arch.boundary_length());
+        let (header, body) = match &entry.data {
+            HrxEntryData::File { body } => (format!("{} {}", bound, fname), body.as_ref().map(|s| &s[..]).unwrap_or("")),
+            HrxEntryData::Directory => (format!("{} {}/", bound, fname), ""),
         };
 
-        let dest_name = dest_name.ok_or(wcxhead::E_NOT_SUPPORTED)?;
-        let mut out_f = File::create(if let Some(dest_path) = dest_path {
-                Cow::from(dest_path.join(dest_name))
+        // The first boundary in the archive isn't preceded by a newline
+        let raw = &self.raw;
+        let header_end = if raw.starts_with(&header[1..]) {
+                Some(header.len() - 1)
             } else {
-                Cow::from(dest_name)
-            }).map_err(|_| wcxhead::E_ECREATE)?;
+                None
+            }
+            .into_iter()
+            .chain(raw.match_indices(&header[..]).map(|(idx, _)| idx + header.len()))
+            .find(|&idx| raw[idx..].is_empty() || raw[idx..].starts_with('\n'))
+            .ok_or_else(|| err(Error::BadArchive))?;
 
-        out_f.write_all(data.as_bytes()).map_err(|_| wcxhead::E_EWRITE)?;
+        let rest = &raw[header_end..];
+        let raw_body = if rest.starts_with(&bound[..]) || rest.is_empty() {
+            Some("")
+        } else {
+            rest.get(1..rest.find(&bound[..]).unwrap_or(rest.len()))
+        };
+
+        if raw_body.map(|b| b.len() == body.len() && b == body) != Some(true) {
+            return Err(err(Error::BadData));
+        }
+
+        // Checked against the original bytes, so this also catches bodies that don't decode
+        let raw_crc = match &entry.data {
+            HrxEntryData::File { .. } => {
+                body_data(raw_body.unwrap_or(""), entry.comment.as_ref().map(|s| &s[..]), self.config.line_endings).map(|data| crc32(&data))
+            }
+            HrxEntryData::Directory => Some(0),
+        };
+        if raw_crc.is_none() || raw_crc != self.current_crc().ok() {
+            return Err(err(Error::BadData));
+        }
+
+        Ok(())
+    }
+
+    /// Extract the current entry to `dest_name`, or to `dest_name` under `dest_path`, if specified
+    ///
+    /// Entries whose host names aren't safe to extract as-is (see [`sanitise_entry_path()`](util/fn.sanitise_entry_path.html)) and
+    /// targets containing `..` components, or resolving, symlinks followed, to outside of `dest_path`, are refused with
+    /// [`E_UNSAFE_PATH`](constant.E_UNSAFE_PATH.html), before anything is written. Without `dest_path`, `dest_name` is the full
+    /// path, and is kept within the directory it has the entry's host name under, or its parent, if it doesn't end in that.
+    ///
+    /// The written file is read back, and if its contents don't match [`current_crc()`](#method.current_crc), it's removed and
+    /// `E_BAD_DATA` returned.
+    pub fn extract_current_entry<Pd: AsRef<Path>, Pn: AsRef<Path>>(&self, dest_path: Option<Pd>, dest_name: Option<Pn>) -> Result<(), Error> {
+        self.extract_current_entry_impl(dest_path.as_ref().map(AsRef::as_ref), dest_name.as_ref().map(AsRef::as_ref))
+    }
+
+    fn extract_current_entry_impl(&self, dest_path: Option<&Path>, dest_name: Option<&Path>) -> Result<(), Error> {
+        let (_, entry, host_name) = self.current_entry().ok_or(Error::EndArchive)?;
+        if sanitise_entry_path(host_name) != host_name {
+            return Err(Error::UnsafePath);
+        }
+
+        let data = entry_data(entry, self.config.line_endings)?;
+        let expected_crc = self.current_crc()?;
 
-        if if let Some(cbk) = self.process_data_callback {
-            cbk(ptr::null_mut(), data.len() as i32) == 0
-        } else if let Some(cbk) = self.process_data_callback_w {
-            cbk(ptr::null_mut(), data.len() as i32) == 0
+        let dest_name = dest_name.ok_or(Error::NotSupported)?;
+        if dest_name.components().any(|c| c == Component::ParentDir) {
+            return Err(Error::UnsafePath);
+        }
+        let (root, dest) = if let Some(dest_path) = dest_path {
+            if dest_name.has_root() || dest_name.components().any(|c| matches!(c, Component::Prefix(_))) {
+                return Err(Error::UnsafePath);
+            }
+
+            (Cow::from(dest_path), Cow::from(dest_path.join(dest_name)))
         } else {
-            false
-        } {
-            Err(wcxhead::E_EABORTED)
+            (Cow::from(extraction_root(dest_name, host_name)), Cow::from(dest_name))
+        };
+        match (resolve_path(&root), resolve_path(&dest)) {
+            (Some(root), Some(resolved)) if resolved.starts_with(&root) => {}
+            _ => return Err(Error::UnsafePath),
+        }
+
+        let name = dest.to_string_lossy();
+        match data {
+            Some(data) => {
+                // The host usually creates the parent directories, but only if they were listed
+                if let Some(parent) = dest.parent() {
+                    fs::create_dir_all(parent).map_err(|_| Error::Create)?;
+                }
+
+                let mut out_f = File::create(&dest).map_err(|_| Error::Create)?;
+                if data.is_empty() && self.progress.data_processed(&name, 0) {
+                    return Err(Error::Aborted);
+                }
+                for chunk in data.chunks(ProgressCallbacks::CHUNK_SIZE) {
+                    out_f.write_all(chunk).map_err(|_| Error::Write)?;
+
+                    if self.progress.data_processed(&name, chunk.len()) {
+                        drop(out_f);
+                        let _ = fs::remove_file(&dest);
+                        return Err(Error::Aborted);
+                    }
+                }
+                // Read back, since that's what ended up on disk
+                if file_crc(&dest).ok() != Some(expected_crc) {
+                    drop(out_f);
+                    let _ = fs::remove_file(&dest);
+                    return Err(Error::BadData);
+                }
+
+                if let Some(mtime) = entry_mod_time(entry) {
+                    out_f.set_modified(mtime).map_err(|_| Error::Write)?;
+                }
+            }
+            None => {
+                fs::create_dir_all(&dest).map_err(|_| Error::Create)?;
+                if self.progress.file_percent(&name, 100) {
+                    return Err(Error::Aborted);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+
+/// Get the CRC-32 of the contents of the specified file, reading it `ProgressCallbacks::CHUNK_SIZE` bytes at a time
+fn file_crc(path: &Path) -> io::Result<u32> {
+    let mut file = File::open(path)?;
+    let mut buf = vec![0; ProgressCallbacks::CHUNK_SIZE];
+
+    let mut crc = 0;
+    loop {
+        match file.read(&mut buf)? {
+            0 => return Ok(crc),
+            read => crc = crc32_update(crc, &buf[..read]),
+        }
+    }
+}
+
+/// Resolve the symlinks in the existing part of the specified path, leaving the rest as-is
+///
+/// Returns `None` if that fails, as it would for dangling symlinks.
+fn resolve_path(path: &Path) -> Option<PathBuf> {
+    for ancestor in path.ancestors() {
+        let existing = if ancestor.as_os_str().is_empty() {
+            Path::new(".")
         } else {
-            Ok(())
+            ancestor
+        };
+
+        if fs::symlink_metadata(existing).is_ok() {
+            return fs::canonicalize(existing).ok().map(|existing| existing.join(path.strip_prefix(ancestor).unwrap()));
+        }
+    }
+
+    None
+}
+
+/// Get the directory the host extracts to, given the full path it extracts the entry with the specified host name to
+///
+/// That's `dest_name` without the components of the host name, if it ends in them, and its parent otherwise, as when the entry is
+/// extracted under another name.
+fn extraction_root<'d>(dest_name: &'d Path, host_name: &str) -> &'d Path {
+    let depth = host_name.split('/').count();
+    let ends_in_host_name = dest_name.components().count() > depth &&
+                            dest_name.components().rev().zip(host_name.split('/').rev()).all(|(d, h)| d == Component::Normal(h.as_ref()));
+
+    let root = if ends_in_host_name {
+        dest_name.ancestors().nth(depth)
+    } else {
+        dest_name.parent()
+    };
+    root.unwrap_or_else(|| Path::new(""))
+}
+
+/// Get the data the specified entry extracts to, decoded if need be, or `None` for directories
+fn entry_data(entry: &HrxEntry, line_endings: LineEndings) -> Result<Option<Cow<'_, [u8]>>, Error> {
+    match &entry.data {
+        HrxEntryData::File { body } => {
+            body_data(body.as_ref().map(|s| &s[..]).unwrap_or(""), entry.comment.as_ref().map(|s| &s[..]), line_endings).map(Some).ok_or(Error::BadData)
         }
+        HrxEntryData::Directory => Ok(None),
     }
 }
+
+/// Decode the specified body, if its entry comment marks it as base64, or restore the line endings it records, or convert them
+/// to CRLF if requested, returning `None` if it doesn't decode
+fn body_data<'b>(body: &'b str, comment: Option<&str>, line_endings: LineEndings) -> Option<Cow<'b, [u8]>> {
+    if comment_tag(comment, BASE64_TAG.0) == Some(BASE64_TAG.1) {
+        base64_decode(body).map(Cow::from)
+    } else if let Some(description) = comment_tag(comment, LINE_ENDINGS_TAG) {
+        restore_line_endings(body, description).map(|body| Cow::from(body.into_bytes()))
+    } else if line_endings == LineEndings::Crlf {
+        Some(match crlf_line_endings(body) {
+            Cow::Borrowed(body) => Cow::from(body.as_bytes()),
+            Cow::Owned(body) => Cow::from(body.into_bytes()),
+        })
+    } else {
+        Some(Cow::from(body.as_bytes()))
+    }
+}
+
+/// Get the modification time recorded in the comment of the specified entry, if any
+pub fn entry_mod_time(entry: &HrxEntry) -> Option<SystemTime> {
+    comment_tag(entry.comment.as_ref().map(|s| &s[..]), MTIME_TAG).and_then(rfc3339_to_system_time)
+}
+
+/// Get the comment of the specified entry as exposed by its comment sidecar, i.e. without the plugin-managed tags
+///
+/// Returns `None` if there's nothing left.
+pub fn sidecar_comment(entry: &HrxEntry) -> Option<String> {
+    let mut comment = entry.comment.clone();
+    for tag in MANAGED_TAGS {
+        set_comment_tag(&mut comment, tag, None);
+    }
+    comment
+}
+
+/// Set the comment of the specified entry as exposed by its comment sidecar, keeping the plugin-managed tags as they were
+pub fn set_sidecar_comment(entry: &mut HrxEntry, comment: Option<String>) {
+    let previous_comment = mem::replace(&mut entry.comment, comment);
+    for tag in MANAGED_TAGS {
+        set_comment_tag(&mut entry.comment, tag, comment_tag(previous_comment.as_ref().map(|s| &s[..]), tag));
+    }
+}
+
+/// Find all entries listed without being in the specified archive
+///
+/// These are directories implied by, but not explicitly listed in, the archive, ordered by the index of the first entry they
+/// contain, and then by depth, and, if requested, comment sidecars, right after the entries they expose the comments of.
+pub fn virtual_entries(arch: &HrxArchive, comment_sidecars: bool) -> Vec<(usize, HrxPath, HrxEntry)> {
+    let mut seen = HashSet::new();
+
+    let mut ret = vec![];
+    for (idx, (path, entry)) in arch.entries.iter().enumerate() {
+        let path = path.as_ref();
+
+        for (slash_idx, _) in path.match_indices('/') {
+            let dir = &path[..slash_idx];
+
+            if !arch.entries.contains_key(dir) && seen.insert(dir) {
+                if let Ok(dir) = dir.parse() {
+                    ret.push((idx,
+                              dir,
+                              HrxEntry {
+                                  comment: None,
+                                  data: HrxEntryData::Directory,
+                              }));
+                }
+            }
+        }
+
+        if comment_sidecars {
+            let sidecar_path = format!("{}{}", path, COMMENT_SIDECAR_SUFFIX);
+
+            if !arch.entries.contains_key(&sidecar_path[..]) {
+                if let (Some(comment), Ok(sidecar_path)) = (sidecar_comment(entry), sidecar_path.parse()) {
+                    ret.push((idx + 1,
+                              sidecar_path,
+                              HrxEntry {
+                                  comment: None,
+                                  data: HrxEntryData::File { body: Some(comment) },
+                              }));
+                }
+            }
+        }
+    }
+
+    ret
+}
+
+/// Interleave the real and virtual entries in the order they're listed in
+fn listing(arch: &HrxArchive, virtual_entries: &[(usize, HrxPath, HrxEntry)]) -> Vec<ListedEntry> {
+    let mut virtual_entries = virtual_entries.iter().enumerate().peekable();
+
+    let mut order = vec![];
+    for (idx, path) in arch.entries.keys().map(Some).chain(iter::once(None)).enumerate() {
+        while let Some((virtual_idx, (_, virtual_path, _))) = virtual_entries.next_if(|(_, (before_idx, _, _))| *before_idx == idx) {
+            order.push((virtual_path, Some(virtual_idx)));
+        }
+        if let Some(path) = path {
+            order.push((path, None));
+        }
+    }
+
+    let mut host_names = host_names(order.iter().map(|(path, _)| path.as_ref()));
+    order.into_iter()
+        .map(|(path, virtual_idx)| {
+            ListedEntry {
+                host_name: host_names.remove(path.as_ref()).unwrap_or_else(|| path.to_string()),
+                path: path.clone(),
+                virtual_idx: virtual_idx,
+                extracted: Cell::new(None),
+            }
+        })
+        .collect()
+}
+
+/// Get the paths of all entries, real or virtual, listed for the specified archive, alongside the names they're presented to the
+/// host as, in the order they're listed in
+pub fn listed_host_names(arch: &HrxArchive, comment_sidecars: bool) -> Vec<(HrxPath, String)> {
+    listing(arch, &virtual_entries(arch, comment_sidecars)).into_iter().map(|listed| (listed.path, listed.host_name)).collect()
+}
+
+/// Map the specified paths, and all of their parent directories, to names Windows can represent
+///
+/// Each component is escaped with [`escape_entry_name()`](util/fn.escape_entry_name.html), and the ones that'd end up differing
+/// only in case from one seen earlier get [`suffix_entry_name()`](util/fn.suffix_entry_name.html)ed with the lowest free number.
+pub fn host_names<'a, I: IntoIterator<Item = &'a str>>(paths: I) -> HashMap<String, String> {
+    let mut taken = HashSet::new();
+
+    let mut ret: HashMap<String, String> = HashMap::new();
+    for path in paths {
+        let mut start = 0;
+        for end in path.match_indices('/').map(|(idx, _)| idx).chain(iter::once(path.len())) {
+            if !ret.contains_key(&path[..end]) {
+                let parent = if start == 0 {
+                    ""
+                } else {
+                    &ret[&path[..start - 1]][..]
+                };
+                let name = escape_entry_name(&path[start..end]);
+
+                let mut host_name = if parent.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{}/{}", parent, name)
+                };
+                let mut n = 1;
+                while !taken.insert(host_name.to_lowercase()) {
+                    host_name = if parent.is_empty() {
+                        suffix_entry_name(&name, n)
+                    } else {
+                        format!("{}/{}", parent, suffix_entry_name(&name, n))
+                    };
+                    n += 1;
+                }
+
+                ret.insert(path[..end].to_string(), host_name);
+            }
+
+            start = end + 1;
+        }
+    }
+
+    ret
+}
@@ -1,9 +1,20 @@
 //! Various utility functions
 
 
+use hrx::{HrxEntryData, HrxArchive, HrxPath};
 use std::time::{SystemTime, Duration};
-use libc::{tm, time_t, c_int};
-use std::{slice, ptr};
+use std::collections::BTreeSet;
+use std::borrow::Cow;
+use libc::{tm, time_t, c_uint, c_int};
+#[cfg(windows)]
+use winapi::um::stringapiset::{MultiByteToWideChar, WideCharToMultiByte};
+#[cfg(windows)]
+use winapi::um::winnls::CP_ACP;
+#[cfg(windows)]
+use libc::c_char;
+use std::convert::TryInto;
+use std::num::NonZeroUsize;
+use std::{slice, iter, ptr};
 use num_traits::Num;
 
 
@@ -53,6 +64,830 @@ impl<T: 'static + Num> Iterator for CListIter<T> {
 }
 
 
+/// Decode the specified NUL-terminated UTF-16 string, replacing unpaired surrogates
+///
+/// Null pointers decode to the empty string.
+///
+/// # Safety
+///
+/// `s` must be null or point to a NUL-terminated string.
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::wide_to_string;
+/// # use std::ptr;
+/// let wide = "dir\\żółw.txt\0".encode_utf16().collect::<Vec<_>>();
+/// assert_eq!(unsafe { wide_to_string(wide.as_ptr()) }, "dir\\żółw.txt");
+///
+/// assert_eq!(unsafe { wide_to_string([0xD800, 0x41, 0].as_ptr()) }, "\u{FFFD}A");
+/// assert_eq!(unsafe { wide_to_string(ptr::null()) }, "");
+/// ```
+pub unsafe fn wide_to_string(s: *const u16) -> String {
+    if s.is_null() {
+        return String::new();
+    }
+
+    let mut len = 0;
+    while *s.add(len) != 0 {
+        len += 1;
+    }
+    String::from_utf16_lossy(slice::from_raw_parts(s, len))
+}
+
+/// Decode the specified string from the ANSI code page, replacing what doesn't map
+///
+/// That's the system one on Windows, and UTF-8 elsewhere, as Double Commander passes it.
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::ansi_to_string;
+/// assert_eq!(ansi_to_string(b"dir\\file.txt"), "dir\\file.txt");
+/// ```
+#[cfg(windows)]
+pub fn ansi_to_string(s: &[u8]) -> String {
+    let len = s.len().try_into().unwrap_or(c_int::MAX);
+    let wide_len = unsafe { MultiByteToWideChar(CP_ACP, 0, s.as_ptr() as *const c_char, len, ptr::null_mut(), 0) };
+
+    let mut wide = vec![0u16; wide_len.max(0) as usize];
+    let wide_len = unsafe { MultiByteToWideChar(CP_ACP, 0, s.as_ptr() as *const c_char, len, wide.as_mut_ptr(), wide_len) };
+    String::from_utf16_lossy(&wide[..wide_len.max(0) as usize])
+}
+
+/// Decode the specified string from the ANSI code page, replacing what doesn't map
+///
+/// That's the system one on Windows, and UTF-8 elsewhere, as Double Commander passes it.
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::ansi_to_string;
+/// assert_eq!(ansi_to_string(b"dir/file.txt"), "dir/file.txt");
+/// ```
+#[cfg(not(windows))]
+pub fn ansi_to_string(s: &[u8]) -> String {
+    String::from_utf8_lossy(s).into_owned()
+}
+
+/// Encode the specified string into the ANSI code page, replacing what doesn't map with its default character
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::string_to_ansi;
+/// assert_eq!(string_to_ansi("dir\\file.txt"), b"dir\\file.txt");
+/// ```
+#[cfg(windows)]
+pub fn string_to_ansi(s: &str) -> Vec<u8> {
+    let wide = s.encode_utf16().collect::<Vec<_>>();
+    let wide_len = wide.len().try_into().unwrap_or(c_int::MAX);
+    let len = unsafe { WideCharToMultiByte(CP_ACP, 0, wide.as_ptr(), wide_len, ptr::null_mut(), 0, ptr::null(), ptr::null_mut()) };
+
+    let mut ret = vec![0u8; len.max(0) as usize];
+    let len = unsafe { WideCharToMultiByte(CP_ACP, 0, wide.as_ptr(), wide_len, ret.as_mut_ptr() as *mut c_char, len, ptr::null(), ptr::null_mut()) };
+    ret.truncate(len.max(0) as usize);
+    ret
+}
+
+/// Encode the specified string into the ANSI code page, replacing what doesn't map with its default character
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::string_to_ansi;
+/// assert_eq!(string_to_ansi("dir/file.txt"), b"dir/file.txt");
+/// ```
+#[cfg(not(windows))]
+pub fn string_to_ansi(s: &str) -> Vec<u8> {
+    s.as_bytes().to_vec()
+}
+
+
+/// The path separator the host presents and passes entry names with
+pub const HOST_PATH_SEPARATOR: char = if cfg!(windows) { '\\' } else { '/' };
+
+/// Convert a path from the host's separators to the `/`-separated one used in archives
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::host_to_entry_path;
+/// if cfg!(windows) {
+///     assert_eq!(host_to_entry_path("dir\\file.txt"), "dir/file.txt");
+/// } else {
+///     assert_eq!(host_to_entry_path("dir/file.txt"), "dir/file.txt");
+///     assert_eq!(host_to_entry_path("back\\slash.txt"), "back\\slash.txt");
+/// }
+/// ```
+pub fn host_to_entry_path(path: &str) -> Cow<'_, str> {
+    if HOST_PATH_SEPARATOR != '/' && path.contains(HOST_PATH_SEPARATOR) {
+        Cow::from(path.replace(HOST_PATH_SEPARATOR, "/"))
+    } else {
+        Cow::from(path)
+    }
+}
+
+/// Convert a `/`-separated path to the host's separators
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::{entry_to_host_path, HOST_PATH_SEPARATOR};
+/// assert_eq!(entry_to_host_path("dir/file.txt"), format!("dir{}file.txt", HOST_PATH_SEPARATOR));
+/// ```
+pub fn entry_to_host_path(path: &str) -> Cow<'_, str> {
+    if HOST_PATH_SEPARATOR != '/' && path.contains('/') {
+        Cow::from(path.replace('/', &HOST_PATH_SEPARATOR.to_string()))
+    } else {
+        Cow::from(path)
+    }
+}
+
+/// Check whether the specified `/`-separated path matches a Totalcmd wildcard pattern
+///
+/// `*` matches any amount of characters and `?` matches exactly one, neither match the `/` separator.
+///
+/// A pattern component ending in `.*` also matches names without an extension, so `*.*` matches everything.
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::wildcard_match;
+/// assert!(wildcard_match("dir/*.*", "dir/file.txt"));
+/// assert!(wildcard_match("dir/*.*", "dir/Makefile"));
+/// assert!(wildcard_match("dir/?ile.t*", "dir/file.txt"));
+/// assert!(wildcard_match("dir/file.txt", "dir/file.txt"));
+///
+/// assert!(!wildcard_match("dir/*.*", "dir"));
+/// assert!(!wildcard_match("dir/*.*", "dir/subdir/file.txt"));
+/// assert!(!wildcard_match("*.txt", "dir/file.txt"));
+/// assert!(!wildcard_match("dir/?.txt", "dir/file.txt"));
+/// ```
+pub fn wildcard_match(pattern: &str, path: &str) -> bool {
+    let mut pattern = pattern.split('/');
+    let mut path = path.split('/');
+
+    loop {
+        match (pattern.next(), path.next()) {
+            (Some(pattern), Some(name)) => {
+                if !wildcard_match_component(pattern, name) {
+                    return false;
+                }
+            }
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+fn wildcard_match_component(pattern: &str, name: &str) -> bool {
+    if pattern.ends_with(".*") && wildcard_match_component(&pattern[..pattern.len() - 2], name) {
+        return true;
+    }
+
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+
+    let (mut pattern_idx, mut name_idx) = (0, 0);
+    let mut last_star = None;
+    while name_idx < name.len() {
+        match pattern.get(pattern_idx) {
+            Some('*') => {
+                last_star = Some((pattern_idx, name_idx));
+                pattern_idx += 1;
+            }
+            Some(&c) if c == '?' || c == name[name_idx] => {
+                pattern_idx += 1;
+                name_idx += 1;
+            }
+            _ => {
+                match last_star {
+                    // Backtrack, making the last star match one more character
+                    Some((star_pattern_idx, star_name_idx)) => {
+                        last_star = Some((star_pattern_idx, star_name_idx + 1));
+                        pattern_idx = star_pattern_idx + 1;
+                        name_idx = star_name_idx + 1;
+                    }
+                    None => return false,
+                }
+            }
+        }
+    }
+
+    pattern[pattern_idx..].iter().all(|&c| c == '*')
+}
+
+/// Make an archive-side path safe to hand to the host as a name relative to the extraction directory
+///
+/// Both `/` and `\` are taken as separators, and empty components, like the ones leading absolute and UNC paths, are dropped.
+/// Components Windows would resolve to `.` or `..` after stripping trailing dots and spaces are replaced with `_`, as are colons,
+/// which would otherwise denote drives or alternate data streams. The result is `/`-separated.
+///
+/// Returns the path unchanged if it's already safe.
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::sanitise_entry_path;
+/// assert_eq!(sanitise_entry_path("dir/file.txt"), "dir/file.txt");
+/// assert_eq!(sanitise_entry_path(".hidden/file."), ".hidden/file.");
+///
+/// assert_eq!(sanitise_entry_path("../file.txt"), "_/file.txt");
+/// assert_eq!(sanitise_entry_path("dir/.. /. ./file.txt"), "dir/_/_/file.txt");
+/// assert_eq!(sanitise_entry_path("/etc/passwd"), "etc/passwd");
+/// assert_eq!(sanitise_entry_path("C:\\Windows\\win.ini"), "C_/Windows/win.ini");
+/// assert_eq!(sanitise_entry_path("\\\\server\\share\\file.txt"), "server/share/file.txt");
+/// assert_eq!(sanitise_entry_path("file.txt:stream"), "file.txt_stream");
+/// assert_eq!(sanitise_entry_path("/"), "_");
+/// ```
+pub fn sanitise_entry_path(path: &str) -> Cow<'_, str> {
+    let mut ret = String::with_capacity(path.len());
+    for component in path.split(['/', '\\']).filter(|c| !c.is_empty()) {
+        if !ret.is_empty() {
+            ret.push('/');
+        }
+
+        if component.trim_end_matches(['.', ' ']).is_empty() {
+            ret.push('_');
+        } else {
+            ret.extend(component.chars().map(|c| if c == ':' { '_' } else { c }));
+        }
+    }
+    if ret.is_empty() {
+        ret.push('_');
+    }
+
+    if ret == path {
+        Cow::from(path)
+    } else {
+        Cow::from(ret)
+    }
+}
+
+/// Characters Windows doesn't allow in file names, besides the separators and control characters
+const WINDOWS_ILLEGAL_CHARS: &[char] = &['<', '>', ':', '"', '|', '?', '*'];
+
+/// Names Windows reserves for devices, regardless of extension
+const WINDOWS_RESERVED_NAMES: &[&str] = &["CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1",
+                                          "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9"];
+
+
+/// Escape a single path component into a name Windows can represent, reversibly with
+/// [`unescape_entry_path()`](fn.unescape_entry_path.html)
+///
+/// Offending characters are replaced with `%XX`, their hexadecimal codepoint: characters Windows doesn't allow,
+/// trailing dots and spaces, and the first character of reserved device names.
+///
+/// A `%` is only escaped if it'd otherwise be taken for an escape sequence, i.e. if followed by two hexadecimal digits or `~`.
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::escape_entry_name;
+/// assert_eq!(escape_entry_name("file.txt"), "file.txt");
+/// assert_eq!(escape_entry_name("100%.txt"), "100%.txt");
+///
+/// assert_eq!(escape_entry_name("CON"), "%43ON");
+/// assert_eq!(escape_entry_name("aux.txt"), "%61ux.txt");
+/// assert_eq!(escape_entry_name("file. "), "file.%20");
+/// assert_eq!(escape_entry_name("what?*.txt"), "what%3F%2A.txt");
+/// assert_eq!(escape_entry_name("100%41"), "100%2541");
+/// ```
+pub fn escape_entry_name(name: &str) -> Cow<'_, str> {
+    let stem = name.split('.').next().unwrap_or("").trim_end_matches(' ');
+    let reserved = WINDOWS_RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem));
+
+    let escape = |(idx, c): (usize, char)| {
+        c < ' ' || WINDOWS_ILLEGAL_CHARS.contains(&c) || (idx == 0 && reserved) || (idx + c.len_utf8() == name.len() && (c == '.' || c == ' ')) ||
+        (c == '%' && escape_sequence_len(&name[idx..]).is_some())
+    };
+
+    if !name.char_indices().any(escape) {
+        return Cow::from(name);
+    }
+
+    let mut ret = String::with_capacity(name.len() + 2);
+    for (idx, c) in name.char_indices() {
+        if escape((idx, c)) {
+            ret.push_str(&format!("%{:02X}", c as u32));
+        } else {
+            ret.push(c);
+        }
+    }
+    Cow::from(ret)
+}
+
+/// Append a `%~N` suffix to an escaped name, before its extension, if any, to tell it apart from ones differing only in case
+///
+/// The suffix is dropped by [`unescape_entry_path()`](fn.unescape_entry_path.html).
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::suffix_entry_name;
+/// assert_eq!(suffix_entry_name("README.md", 1), "README%~1.md");
+/// assert_eq!(suffix_entry_name("Makefile", 2), "Makefile%~2");
+/// assert_eq!(suffix_entry_name(".gitignore", 3), ".gitignore%~3");
+/// ```
+pub fn suffix_entry_name(name: &str, n: usize) -> String {
+    match name.rfind('.') {
+        Some(dot) if dot != 0 => format!("{}%~{}{}", &name[..dot], n, &name[dot..]),
+        _ => format!("{}%~{}", name, n),
+    }
+}
+
+/// Reverse [`escape_entry_name()`](fn.escape_entry_name.html) and [`suffix_entry_name()`](fn.suffix_entry_name.html) for every
+/// component of the specified path
+///
+/// Components are only decoded if escaping the result gives them back, so names with a `%` that isn't an escape, like files
+/// packed from disk, are kept as-is. In particular, `%2F` is never decoded into a separator.
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::unescape_entry_path;
+/// assert_eq!(unescape_entry_path("dir/file.txt"), "dir/file.txt");
+/// assert_eq!(unescape_entry_path("100%.txt"), "100%.txt");
+///
+/// assert_eq!(unescape_entry_path("%43ON/%61ux.txt"), "CON/aux.txt");
+/// assert_eq!(unescape_entry_path("file.%20"), "file. ");
+/// assert_eq!(unescape_entry_path("README%~1.md"), "README.md");
+/// assert_eq!(unescape_entry_path("100%2541"), "100%41");
+///
+/// assert_eq!(unescape_entry_path("100%41.txt"), "100%41.txt");
+/// assert_eq!(unescape_entry_path("a%2Fb.txt"), "a%2Fb.txt");
+/// assert_eq!(unescape_entry_path("file%2E.txt"), "file%2E.txt");
+/// ```
+pub fn unescape_entry_path(path: &str) -> Cow<'_, str> {
+    if !path.match_indices('%').any(|(idx, _)| escape_sequence_len(&path[idx..]).is_some()) {
+        return Cow::from(path);
+    }
+
+    let mut ret = String::with_capacity(path.len());
+    for (idx, component) in path.split('/').enumerate() {
+        if idx != 0 {
+            ret.push('/');
+        }
+        ret.push_str(&unescape_entry_name(component));
+    }
+    Cow::from(ret)
+}
+
+fn unescape_entry_name(name: &str) -> Cow<'_, str> {
+    let name = unsuffix_entry_name(name);
+
+    let mut ret = String::with_capacity(name.len());
+    let mut rest = &name[..];
+    while let Some(pct) = rest.find('%') {
+        ret.push_str(&rest[..pct]);
+        rest = &rest[pct..];
+
+        match escape_sequence_len(rest) {
+            Some(3) => {
+                match u32::from_str_radix(&rest[1..3], 16).ok().and_then(char::from_u32) {
+                    Some(c) if c != '/' => ret.push(c),
+                    _ => return name,
+                }
+                rest = &rest[3..];
+            }
+            _ => {
+                ret.push('%');
+                rest = &rest[1..];
+            }
+        }
+    }
+    ret.push_str(rest);
+
+    if escape_entry_name(&ret) == name {
+        Cow::from(ret)
+    } else {
+        name
+    }
+}
+
+/// Strip the suffix [`suffix_entry_name()`](fn.suffix_entry_name.html) would've added, if any
+fn unsuffix_entry_name(name: &str) -> Cow<'_, str> {
+    for (idx, _) in name.rmatch_indices("%~") {
+        if let Some(len) = escape_sequence_len(&name[idx..]) {
+            let unsuffixed = format!("{}{}", &name[..idx], &name[idx + len..]);
+            if let Ok(n) = name[idx + 2..idx + len].parse() {
+                if suffix_entry_name(&unsuffixed, n) == name {
+                    return Cow::from(unsuffixed);
+                }
+            }
+        }
+    }
+
+    Cow::from(name)
+}
+
+/// Parse the specified `/`-separated path into one that can be written into an archive
+///
+/// Paths can't start with a space, since the ones separating them from the boundary would take it with them.
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::archivable_path;
+/// assert_eq!(archivable_path("dir/file.txt").as_ref().map(AsRef::as_ref), Some("dir/file.txt"));
+/// assert_eq!(archivable_path("dir/ file.txt").as_ref().map(AsRef::as_ref), Some("dir/ file.txt"));
+///
+/// assert!(archivable_path(" file.txt").is_none());
+/// assert!(archivable_path("dir/../file.txt").is_none());
+/// assert!(archivable_path("C:/file.txt").is_none());
+/// ```
+pub fn archivable_path(path: &str) -> Option<HrxPath> {
+    if path.starts_with(' ') {
+        return None;
+    }
+
+    path.parse().ok()
+}
+
+/// Get the length of the `%XX` escape or `%~N` suffix the specified string starts with
+fn escape_sequence_len(s: &str) -> Option<usize> {
+    let b = s.as_bytes();
+    if b.len() >= 3 && b[0] == b'%' && b[1].is_ascii_hexdigit() && b[2].is_ascii_hexdigit() {
+        Some(3)
+    } else if b.len() >= 3 && b[0] == b'%' && b[1] == b'~' && b[2].is_ascii_digit() {
+        Some(2 + b[2..].iter().take_while(|b| b.is_ascii_digit()).count())
+    } else {
+        None
+    }
+}
+
+/// Find the shortest boundary length, but no shorter than `at_least`, that none of the archive's bodies and comments contain
+///
+/// This is done in a single pass over the archive, collecting the lengths of all `<=+>` runs at line starts.
+///
+/// Unlike [`HrxArchive::validate_content()`](../../hrx/struct.HrxArchive.html#method.validate_content), bodies and comments
+/// *starting* with a boundary are also taken into account.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate totalcmd_hrx;
+/// # extern crate hrx;
+/// # use totalcmd_hrx::util::minimal_boundary_length;
+/// # use std::num::NonZeroUsize;
+/// # use hrx::HrxArchive;
+/// # fn main() {
+/// let arch: HrxArchive = "<======> a.hrx\n<===> file\n<==>\n\n<======> b.hrx\n<=> file\n".parse().unwrap();
+/// assert_eq!(arch.boundary_length().get(), 6);
+///
+/// assert_eq!(minimal_boundary_length(&arch, NonZeroUsize::new(1).unwrap()).get(), 4);
+/// assert_eq!(minimal_boundary_length(&arch, NonZeroUsize::new(3).unwrap()).get(), 4);
+/// assert_eq!(minimal_boundary_length(&arch, NonZeroUsize::new(5).unwrap()).get(), 5);
+/// # }
+/// ```
+pub fn minimal_boundary_length(archive: &HrxArchive, at_least: NonZeroUsize) -> NonZeroUsize {
+    let bodies = archive.comment
+        .iter()
+        .chain(archive.entries.values().flat_map(|e| {
+            e.comment.iter().chain(match e.data {
+                HrxEntryData::File { body: Some(ref body) } => Some(body),
+                _ => None,
+            })
+        }));
+
+    let mut taken = BTreeSet::new();
+    for body in bodies {
+        for line_start in iter::once(0).chain(body.match_indices('\n').map(|(idx, _)| idx + 1)) {
+            let line = &body.as_bytes()[line_start..];
+
+            if line.first() == Some(&b'<') {
+                let run = line[1..].iter().take_while(|&&b| b == b'=').count();
+                if run != 0 && line.get(1 + run) == Some(&b'>') {
+                    taken.insert(run);
+                }
+            }
+        }
+    }
+
+    let mut len = at_least.get();
+    for &run in taken.range(len..) {
+        if run != len {
+            break;
+        }
+        len += 1;
+    }
+    NonZeroUsize::new(len).unwrap()
+}
+
+/// Get the value of the `key: value` line in an HRX entry comment
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::comment_tag;
+/// assert_eq!(comment_tag(Some("Whatever\nencoding: base64"), "encoding"), Some("base64"));
+/// assert_eq!(comment_tag(Some("Whatever\nencodings: base64"), "encoding"), None);
+/// assert_eq!(comment_tag(None, "encoding"), None);
+/// ```
+pub fn comment_tag<'c>(comment: Option<&'c str>, key: &str) -> Option<&'c str> {
+    comment.and_then(|c| c.lines().filter_map(|l| comment_tag_value(l, key)).next())
+}
+
+/// Set or, if `value` is `None`, remove the `key: value` line in an HRX entry comment
+///
+/// A comment left empty is removed.
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::set_comment_tag;
+/// let mut comment = Some("Whatever".to_string());
+///
+/// set_comment_tag(&mut comment, "encoding", Some("base64"));
+/// assert_eq!(comment.as_ref().map(|s| &s[..]), Some("Whatever\nencoding: base64"));
+///
+/// set_comment_tag(&mut comment, "encoding", None);
+/// assert_eq!(comment.as_ref().map(|s| &s[..]), Some("Whatever"));
+///
+/// let mut comment = None;
+/// set_comment_tag(&mut comment, "encoding", None);
+/// assert_eq!(comment, None);
+/// ```
+pub fn set_comment_tag(comment: &mut Option<String>, key: &str, value: Option<&str>) {
+    if value.is_none() && comment_tag(comment.as_ref().map(|c| &c[..]), key).is_none() {
+        return;
+    }
+
+    let mut lines = comment.as_ref().map(|c| c.lines().filter(|l| comment_tag_value(l, key).is_none()).collect::<Vec<_>>()).unwrap_or_default();
+
+    let tag;
+    if let Some(value) = value {
+        tag = format!("{}: {}", key, value);
+        lines.push(&tag);
+    }
+
+    *comment = if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    };
+}
+
+fn comment_tag_value<'l>(line: &'l str, key: &str) -> Option<&'l str> {
+    if line.starts_with(key) && line[key.len()..].starts_with(": ") {
+        Some(&line[key.len() + 2..])
+    } else {
+        None
+    }
+}
+
+
+/// Convert the CRLF line endings in the specified text to LF, alongside which lines ended in CRLF, or `None` if none did
+///
+/// Those are described as `crlf` if all lines did, and as `crlf` followed by the ranges of their 1-based numbers otherwise,
+/// which [`restore_line_endings()`](fn.restore_line_endings.html) takes to convert them back.
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::normalise_line_endings;
+/// assert_eq!(normalise_line_endings("a\r\nb\r\nc"), Some(("a\nb\nc".to_string(), "crlf".to_string())));
+/// assert_eq!(normalise_line_endings("a\r\nb\r\nc\nd\r\n\r"),
+///            Some(("a\nb\nc\nd\n\r".to_string(), "crlf 1-2,4".to_string())));
+/// assert_eq!(normalise_line_endings("a\nb\rc"), None);
+/// ```
+pub fn normalise_line_endings(text: &str) -> Option<(String, String)> {
+    let mut normalised = String::with_capacity(text.len());
+    let mut lines = 0;
+    let mut crlf_lines = vec![];
+    for line in text.split_inclusive('\n') {
+        match line.strip_suffix("\r\n") {
+            Some(content) => {
+                normalised.push_str(content);
+                normalised.push('\n');
+                crlf_lines.push(lines + 1);
+            }
+            None => normalised.push_str(line),
+        }
+        if line.ends_with('\n') {
+            lines += 1;
+        }
+    }
+
+    if crlf_lines.is_empty() {
+        return None;
+    }
+
+    let mut description = "crlf".to_string();
+    if crlf_lines.len() != lines {
+        let mut ranges = vec![];
+        for line in crlf_lines {
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == line => *end = line,
+                _ => ranges.push((line, line)),
+            }
+        }
+
+        description.push(' ');
+        description.push_str(&ranges.into_iter()
+            .map(|(start, end)| if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            })
+            .collect::<Vec<_>>()
+            .join(","));
+    }
+
+    Some((normalised, description))
+}
+
+/// Convert the LF line endings of the lines specified as by [`normalise_line_endings()`](fn.normalise_line_endings.html) back
+/// to CRLF, or return `None` if the description is invalid or refers to lines past the end
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::restore_line_endings;
+/// assert_eq!(restore_line_endings("a\nb\nc", "crlf"), Some("a\r\nb\r\nc".to_string()));
+/// assert_eq!(restore_line_endings("a\nb\nc\nd\n\r", "crlf 1-2,4"), Some("a\r\nb\r\nc\nd\r\n\r".to_string()));
+/// assert_eq!(restore_line_endings("a\nb", "crlf 2"), None);
+/// assert_eq!(restore_line_endings("a\nb", "lf"), None);
+/// ```
+pub fn restore_line_endings(text: &str, description: &str) -> Option<String> {
+    let ranges = match description.strip_prefix("crlf") {
+        Some("") => None,
+        Some(ranges) => Some(ranges.strip_prefix(' ')?),
+        None => return None,
+    };
+    let ranges = match ranges {
+        Some(ranges) => {
+            ranges.split(',')
+                .map(|r| {
+                    let (start, end) = r.split_once('-').unwrap_or((r, r));
+                    Some((start.parse::<usize>().ok()?, end.parse::<usize>().ok()?)).filter(|&(start, end)| start != 0 && start <= end)
+                })
+                .collect::<Option<Vec<_>>>()?
+        }
+        None => vec![(1, usize::MAX)],
+    };
+
+    let lines = text.matches('\n').count();
+    if ranges.iter().any(|&(_, end)| end > lines && end != usize::MAX) {
+        return None;
+    }
+
+    let mut restored = String::with_capacity(text.len() + lines);
+    for (idx, line) in text.split_inclusive('\n').enumerate() {
+        match line.strip_suffix('\n') {
+            Some(content) if ranges.iter().any(|&(start, end)| start <= idx + 1 && idx < end) => {
+                restored.push_str(content);
+                restored.push_str("\r\n");
+            }
+            _ => restored.push_str(line),
+        }
+    }
+    Some(restored)
+}
+
+/// Convert every LF line ending in the specified text that isn't CRLF already to CRLF
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::crlf_line_endings;
+/// assert_eq!(crlf_line_endings("a\nb\r\nc\n"), "a\r\nb\r\nc\r\n");
+/// assert_eq!(crlf_line_endings("a\r\nb"), "a\r\nb");
+/// ```
+pub fn crlf_line_endings(text: &str) -> Cow<'_, str> {
+    if !text.split_inclusive('\n').any(|l| l.ends_with('\n') && !l.ends_with("\r\n")) {
+        return Cow::from(text);
+    }
+
+    let mut converted = String::with_capacity(text.len() + text.len() / 8);
+    for line in text.split_inclusive('\n') {
+        match line.strip_suffix('\n') {
+            Some(content) if !content.ends_with('\r') => {
+                converted.push_str(content);
+                converted.push_str("\r\n");
+            }
+            _ => converted.push_str(line),
+        }
+    }
+    Cow::from(converted)
+}
+
+
+/// Encode the specified data in standard padded base64, wrapped at 76 characters
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::base64_encode;
+/// assert_eq!(base64_encode(b"\x00\xFFOwO"), "AP9Pd08=");
+/// assert_eq!(base64_encode(&[0; 60]).lines().map(str::len).collect::<Vec<_>>(), &[76, 4]);
+/// ```
+pub fn base64_encode(data: &[u8]) -> String {
+    let mut ret = String::with_capacity(data.len() / 3 * 4 + data.len() / 57 + 4);
+
+    for (i, chunk) in data.chunks(3).enumerate() {
+        if i != 0 && i % 19 == 0 {
+            ret.push('\n');
+        }
+
+        let group = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+        for j in 0..4 {
+            ret.push(if j <= chunk.len() {
+                BASE64_ALPHABET[(group >> (18 - j * 6)) as usize & 0x3F] as char
+            } else {
+                '='
+            });
+        }
+    }
+
+    ret
+}
+
+/// Decode the specified standard padded base64, ignoring whitespace
+///
+/// Returns `None` if the data isn't valid base64.
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::base64_decode;
+/// assert_eq!(base64_decode("AP9P\nd08="), Some(b"\x00\xFFOwO".to_vec()));
+/// assert_eq!(base64_decode("AP9Pd08"), None);
+/// assert_eq!(base64_decode("AP9P!08="), None);
+/// ```
+pub fn base64_decode(data: &str) -> Option<Vec<u8>> {
+    let data = data.bytes().filter(|b| !b.is_ascii_whitespace()).collect::<Vec<_>>();
+    if data.len() % 4 != 0 {
+        return None;
+    }
+
+    let mut ret = Vec::with_capacity(data.len() / 4 * 3);
+    for (i, chunk) in data.chunks(4).enumerate() {
+        let padding = chunk.iter().rev().take_while(|&&b| b == b'=').count();
+        if padding > 2 || (padding != 0 && i != data.len() / 4 - 1) {
+            return None;
+        }
+
+        let mut group = 0u32;
+        for &b in &chunk[..4 - padding] {
+            group = group << 6 | BASE64_ALPHABET.iter().position(|&a| a == b)? as u32;
+        }
+        group <<= padding * 6;
+
+        ret.extend_from_slice(&[(group >> 16) as u8, (group >> 8) as u8, group as u8][..3 - padding]);
+    }
+
+    Some(ret)
+}
+
+static BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Compute the CRC-32 (as used by zip, in `FileCRC`) of the specified data
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::crc32;
+/// assert_eq!(crc32(b""), 0);
+/// assert_eq!(crc32(b"123456789"), 0xCBF43926);
+/// ```
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0, data)
+}
+
+/// Continue the CRC-32 `crc` of some preceding data over the specified data
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::{crc32_update, crc32};
+/// assert_eq!(crc32_update(crc32(b"1234"), b"56789"), crc32(b"123456789"));
+/// ```
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    !data.iter().fold(!crc, |crc, &b| CRC32_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+static CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { 0xEDB88320 ^ (crc >> 1) } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+}
+
 /// `FileTime` contains the date and the time of the file’s last update. Use the following algorithm to set the value:
 ///
 /// ```c
@@ -77,17 +912,135 @@ impl<T: 'static + Num> Iterator for CListIter<T> {
 pub fn system_time_to_totalcmd_time(tm: &SystemTime) -> c_int {
     let time = unsafe { *localtime(&(tm.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_else(|_| Duration::new(0, 0)).as_secs() as time_t)) };
 
-    let year = (1900 + time.tm_year).max(1980).min(2100);
+    let year = (1900 + time.tm_year).clamp(1980, 2100);
     let month = time.tm_mon + 1;
 
     // fs::write("system_time_to_totalcmd_time.log",
     //           format!("year: {}\nmon : {}\nmday: {}\nhour: {}\nmin : {}\nsec : {}",
     //                   year, month, time.tm_mday, time.tm_hour, time.tm_min, time.tm_sec).as_bytes()).unwrap();
 
-    (year - 1980) << 25 | month << 21 | time.tm_mday << 16 | time.tm_hour << 11 | time.tm_min << 5 | time.tm_sec / 2
+    (year - 1980) << 25 | month << 21 | time.tm_mday << 16 | time.tm_hour << 11 | time.tm_min << 5 | (time.tm_sec / 2)
+}
+
+
+/// Format the specified time as an RFC 3339 UTC timestamp with second precision
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::system_time_to_rfc3339;
+/// # use std::time::{SystemTime, Duration};
+/// assert_eq!(system_time_to_rfc3339(&(SystemTime::UNIX_EPOCH + Duration::from_secs(1714564800))),
+///            "2024-05-01T12:00:00Z");
+/// assert_eq!(system_time_to_rfc3339(&(SystemTime::UNIX_EPOCH - Duration::from_secs(1))),
+///            "1969-12-31T23:59:59Z");
+/// ```
+pub fn system_time_to_rfc3339(tm: &SystemTime) -> String {
+    let secs = match tm.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(since) => since.as_secs() as i64,
+        Err(before) => -(before.duration().as_secs() as i64) - (before.duration().subsec_nanos() != 0) as i64,
+    };
+
+    let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, secs / 3600, secs / 60 % 60, secs % 60)
 }
 
+/// Parse an RFC 3339 UTC timestamp, as produced by [`system_time_to_rfc3339()`](fn.system_time_to_rfc3339.html)
+///
+/// Returns `None` if the timestamp isn't in the `YYYY-MM-DDTHH:MM:SSZ` format.
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::rfc3339_to_system_time;
+/// # use std::time::{SystemTime, Duration};
+/// assert_eq!(rfc3339_to_system_time("2024-05-01T12:00:00Z"),
+///            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1714564800)));
+/// assert_eq!(rfc3339_to_system_time("1969-12-31T23:59:59Z"),
+///            Some(SystemTime::UNIX_EPOCH - Duration::from_secs(1)));
+/// assert_eq!(rfc3339_to_system_time("2024-05-01 12:00:00"), None);
+/// assert_eq!(rfc3339_to_system_time("2024-13-01T12:00:00Z"), None);
+/// ```
+pub fn rfc3339_to_system_time(s: &str) -> Option<SystemTime> {
+    let b = s.as_bytes();
+    if b.len() != 20 || b[4] != b'-' || b[7] != b'-' || b[10] != b'T' || b[13] != b':' || b[16] != b':' || b[19] != b'Z' {
+        return None;
+    }
+
+    let num = |from: usize, to: usize| -> Option<i64> {
+        if b[from..to].iter().all(u8::is_ascii_digit) {
+            s[from..to].parse().ok()
+        } else {
+            None
+        }
+    };
+    let (year, month, day) = (num(0, 4)?, num(5, 7)?, num(8, 10)?);
+    let (hour, minute, second) = (num(11, 13)?, num(14, 16)?, num(17, 19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = year - (month <= 2) as i64;
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    let secs = days * 86400 + hour * 3600 + minute * 60 + second;
+    if secs >= 0 {
+        SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64))
+    } else {
+        SystemTime::UNIX_EPOCH.checked_sub(Duration::from_secs(-secs as u64))
+    }
+}
 
 extern "C" {
     fn localtime(time_p: *const time_t) -> *mut tm;
 }
+
+/// Split a 64-bit size into the low and high 32 bits, as the `*Size` and `*SizeHigh` fields of `tHeaderDataEx` expect
+///
+/// # Examples
+///
+/// ```
+/// # use totalcmd_hrx::util::split_size;
+/// assert_eq!(split_size(0xFFFF_FFFF), (0xFFFF_FFFF, 0));
+/// assert_eq!(split_size(0x1_0000_0000), (0, 1));
+/// assert_eq!(split_size(u64::MAX), (0xFFFF_FFFF, 0xFFFF_FFFF));
+/// ```
+pub fn split_size(size: u64) -> (c_uint, c_uint) {
+    (size as c_uint, (size >> 32) as c_uint)
+}
+
+/// Fit a 64-bit size into the `*Size` fields of `tHeaderData`, saturating at `c_int::MAX`
+///
+/// # Examples
+///
+/// ```
+/// # extern crate totalcmd_hrx;
+/// # extern crate libc;
+/// # use totalcmd_hrx::util::saturate_size;
+/// # use libc::c_int;
+/// # fn main() {
+/// assert_eq!(saturate_size(0x7FFF_FFFF), c_int::MAX);
+/// assert_eq!(saturate_size(0x8000_0000), c_int::MAX);
+/// assert_eq!(saturate_size(0x1_0000_0000), c_int::MAX);
+/// # }
+/// ```
+pub fn saturate_size(size: u64) -> c_int {
+    size.try_into().unwrap_or(c_int::MAX)
+}
@@ -0,0 +1,91 @@
+use totalcmd_hrx::{ProgressCallbacks, LegacyEncoding, BinaryPolicy, ArchiveState, PackFlags, Config, Error, pack_archive};
+use self::super::util::prepare_dir;
+use std::path::Path;
+use std::fs;
+
+
+static FILES: &[(&str, &[u8])] = &[("text.txt", b"Text\n"), ("binary.bin", b"\x80\xFF\xE9\n")];
+
+
+#[test]
+fn skip() {
+    let dir = prepare_dir("binary", "skip");
+
+    assert_eq!(pack(&dir, BinaryPolicy::Skip), Ok(vec!["binary.bin".to_string()]));
+    assert_eq!(fs::read_to_string(dir.join("archive.hrx")).unwrap(), "<===> text.txt\nText\n");
+}
+
+#[test]
+fn skip_everything() {
+    let dir = prepare_dir("binary", "skip_everything");
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("src").join("binary.bin"), b"\xFF").unwrap();
+
+    assert_eq!(pack_archive(dir.join("archive.hrx"),
+                            None::<&str>,
+                            dir.join("src"),
+                            ["binary.bin"].iter(),
+                            PackFlags::default(),
+                            &Config::default(),
+                            &ProgressCallbacks::default()),
+               Err(Error::NoFiles));
+    assert!(!dir.join("archive.hrx").exists());
+}
+
+#[test]
+fn base64() {
+    let dir = prepare_dir("binary", "base64");
+
+    assert_eq!(pack(&dir, BinaryPolicy::Base64), Ok(vec![]));
+    assert_eq!(fs::read_to_string(dir.join("archive.hrx")).unwrap(),
+               "<===> text.txt\nText\n\n<===>\nencoding: base64\n<===> binary.bin\ngP/pCg==");
+    assert_eq!(extract(&dir), FILES.iter().map(|&(_, data)| data.to_vec()).collect::<Vec<_>>());
+}
+
+#[test]
+fn latin1() {
+    let dir = prepare_dir("binary", "latin1");
+
+    assert_eq!(pack(&dir, BinaryPolicy::Transcode(LegacyEncoding::Latin1)), Ok(vec![]));
+    assert_eq!(fs::read_to_string(dir.join("archive.hrx")).unwrap(), "<===> text.txt\nText\n\n<===> binary.bin\n\u{80}ÿé\n");
+    assert_eq!(extract(&dir), vec![b"Text\n".to_vec(), "\u{80}ÿé\n".as_bytes().to_vec()]);
+}
+
+#[test]
+fn windows_1252() {
+    let dir = prepare_dir("binary", "windows_1252");
+
+    assert_eq!(pack(&dir, BinaryPolicy::Transcode(LegacyEncoding::Windows1252)), Ok(vec![]));
+    assert_eq!(fs::read_to_string(dir.join("archive.hrx")).unwrap(), "<===> text.txt\nText\n\n<===> binary.bin\n€ÿé\n");
+}
+
+
+/// Pack `FILES`, from `src/`, into `archive.hrx`
+fn pack(dir: &Path, binary_policy: BinaryPolicy) -> Result<Vec<String>, Error> {
+    fs::create_dir_all(dir.join("src")).unwrap();
+    for &(name, data) in FILES {
+        fs::write(dir.join("src").join(name), data).unwrap();
+    }
+
+    pack_archive(dir.join("archive.hrx"),
+                 None::<&str>,
+                 dir.join("src"),
+                 FILES.iter().map(|&(name, _)| name),
+                 PackFlags::default(),
+                 &Config { binary_policy: binary_policy, ..Config::default() },
+                 &ProgressCallbacks::default())
+}
+
+/// Extract every file in `archive.hrx` into `out/`, returning their contents
+fn extract(dir: &Path) -> Vec<Vec<u8>> {
+    let out = dir.join("out");
+
+    let mut state = ArchiveState::open(dir.join("archive.hrx"), &Config::default()).unwrap();
+    let mut extracted = vec![];
+    while let Some((_, _, name)) = state.next_entry() {
+        let name = name.to_string();
+        assert_eq!(state.extract_current_entry(Some(&out), Some(&name)), Ok(()));
+        extracted.push(fs::read(out.join(&name)).unwrap());
+    }
+    extracted
+}
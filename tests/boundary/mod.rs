@@ -0,0 +1,65 @@
+use totalcmd_hrx::{ProgressCallbacks, PackFlags, Config, pack_archive};
+use self::super::util::prepare_dir;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::fs;
+
+
+#[test]
+fn minimal() {
+    let dir = prepare_dir("boundary", "minimal");
+
+    assert_eq!(pack(&dir, "<===> inner\n<====>\n", &Config::default()), "<=====> a.txt\n<===> inner\n<====>\n");
+}
+
+#[test]
+fn not_at_line_start() {
+    let dir = prepare_dir("boundary", "not_at_line_start");
+
+    assert_eq!(pack(&dir, "x <===> y\n<=>\n", &Config::default()), "<===> a.txt\nx <===> y\n<=>\n");
+}
+
+#[test]
+fn configured() {
+    let dir = prepare_dir("boundary", "configured");
+
+    assert_eq!(pack(&dir, "A\n", &boundary(5, false)), "<=====> a.txt\nA\n");
+    fs::remove_file(dir.join("archive.hrx")).unwrap();
+    assert_eq!(pack(&dir, "<=====>\n", &boundary(5, false)), "<======> a.txt\n<=====>\n");
+}
+
+#[test]
+fn shrunk() {
+    let dir = prepare_dir("boundary", "shrunk");
+
+    fs::write(dir.join("archive.hrx"), "<=======> old.txt\n<====>\n").unwrap();
+    assert_eq!(pack(&dir, "A\n", &boundary(3, false)), "<=======> old.txt\n<====>\n\n<=======> a.txt\nA\n");
+
+    fs::write(dir.join("archive.hrx"), "<=======> old.txt\n<====>\n").unwrap();
+    assert_eq!(pack(&dir, "A\n", &boundary(3, true)), "<===> old.txt\n<====>\n\n<===> a.txt\nA\n");
+}
+
+
+fn boundary(length: usize, shrink: bool) -> Config {
+    Config {
+        boundary_length: NonZeroUsize::new(length).unwrap(),
+        shrink_boundary: shrink,
+        ..Config::default()
+    }
+}
+
+/// Pack `src/a.txt`, holding the specified contents, into `archive.hrx`, returning the result
+fn pack(dir: &Path, contents: &str, config: &Config) -> String {
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("src").join("a.txt"), contents).unwrap();
+
+    assert_eq!(pack_archive(dir.join("archive.hrx"),
+                            None::<&str>,
+                            dir.join("src"),
+                            ["a.txt"].iter(),
+                            PackFlags::default(),
+                            config,
+                            &ProgressCallbacks::default()),
+               Ok(vec![]));
+    fs::read_to_string(dir.join("archive.hrx")).unwrap()
+}
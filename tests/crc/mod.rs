@@ -0,0 +1,49 @@
+use self::super::util::{prepare_dir, open};
+use totalcmd_hrx::Error;
+use std::fs;
+
+
+static ARCHIVE: &str = "<===> text.txt\n123456789\n<===>\nencoding: base64\n<===> binary.bin\nMTIzNDU2Nzg5\n<===> empty.txt\n<===> dir/\n";
+
+
+#[test]
+fn listed() {
+    let dir = prepare_dir("crc", "listed");
+    let mut state = open(&dir, ARCHIVE);
+
+    let mut crcs = vec![];
+    while let Some((path, _, _)) = state.next_entry() {
+        let path = path.to_string();
+        crcs.push((path, state.current_crc()));
+    }
+    assert_eq!(crcs,
+               vec![("text.txt".to_string(), Ok(0xCBF43926)),
+                    ("binary.bin".to_string(), Ok(0xCBF43926)),
+                    ("empty.txt".to_string(), Ok(0)),
+                    ("dir".to_string(), Ok(0))]);
+}
+
+#[test]
+fn tested_and_extracted() {
+    let dir = prepare_dir("crc", "tested_and_extracted");
+    let mut state = open(&dir, ARCHIVE);
+
+    while let Some((_, _, name)) = state.next_entry() {
+        let name = name.to_string();
+        assert_eq!(state.test_current_entry(), Ok(()));
+        assert_eq!(state.extract_current_entry(Some(dir.join("out")), Some(&name)), Ok(()));
+    }
+    assert_eq!(fs::read(dir.join("out").join("binary.bin")).unwrap(), b"123456789");
+}
+
+#[test]
+fn undecodable() {
+    let dir = prepare_dir("crc", "undecodable");
+    let mut state = open(&dir, "<===>\nencoding: base64\n<===> binary.bin\nMTIzNDU2Nzg\n<===> dir/\n");
+
+    assert!(state.next_entry().is_some());
+    assert_eq!(state.current_crc(), Err(Error::BadData));
+    assert_eq!(state.test_current_entry(), Err((Error::BadData, "binary.bin".to_string())));
+    assert_eq!(state.extract_current_entry(Some(dir.join("out")), Some("binary.bin")), Err(Error::BadData));
+    assert!(!dir.join("out").join("binary.bin").exists());
+}
@@ -0,0 +1,155 @@
+use self::super::util::{prepare_dir, open};
+use totalcmd_hrx::Error;
+use std::path::{PathBuf, Path};
+use std::fs;
+
+
+static ARCHIVE: &str = "<===> a.txt\nA\n<===> dir/b.txt\nB\n";
+
+
+#[test]
+fn parent_dir() {
+    let dir = prepare_dir("extract", "parent_dir");
+    let mut state = open(&dir, ARCHIVE);
+    state.next_entry();
+
+    let out = dir.join("out");
+    assert_eq!(state.extract_current_entry(Some(&out), Some(Path::new("..").join("a.txt"))), Err(Error::UnsafePath));
+    assert_eq!(state.extract_current_entry(None::<&Path>, Some(out.join("..").join("a.txt"))), Err(Error::UnsafePath));
+    assert!(!dir.join("a.txt").exists());
+}
+
+#[test]
+fn absolute() {
+    let dir = prepare_dir("extract", "absolute");
+    let mut state = open(&dir, ARCHIVE);
+    state.next_entry();
+
+    assert_eq!(state.extract_current_entry(Some(dir.join("out")), Some(dir.join("a.txt"))), Err(Error::UnsafePath));
+    assert!(!dir.join("a.txt").exists());
+}
+
+#[test]
+#[cfg(windows)]
+fn drive() {
+    let dir = prepare_dir("extract", "drive");
+    let mut state = open(&dir, ARCHIVE);
+    state.next_entry();
+
+    assert_eq!(state.extract_current_entry(Some(dir.join("out")), Some("C:a.txt")), Err(Error::UnsafePath));
+    assert_eq!(state.extract_current_entry(Some(dir.join("out")), Some("\\\\?\\C:\\a.txt")), Err(Error::UnsafePath));
+}
+
+#[test]
+fn full_dest_name() {
+    let dir = prepare_dir("extract", "full_dest_name");
+    let mut state = open(&dir, ARCHIVE);
+    state.next_entry();
+    state.next_entry();
+    state.next_entry();
+
+    let out = dir.join("out");
+    assert_eq!(state.extract_current_entry(None::<&Path>, Some(out.join("dir").join("b.txt"))), Ok(()));
+    assert_eq!(state.extract_current_entry(None::<&Path>, Some(out.join("renamed.txt"))), Ok(()));
+    assert_eq!(fs::read_to_string(out.join("dir").join("b.txt")).unwrap(), "B\n");
+    assert_eq!(fs::read_to_string(out.join("renamed.txt")).unwrap(), "B\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn symlink() {
+    let dir = prepare_dir("extract", "symlink");
+    let mut state = open(&dir, ARCHIVE);
+    state.next_entry();
+    state.next_entry();
+    state.next_entry();
+
+    let out = dir.join("out");
+    fs::create_dir_all(&out).unwrap();
+    fs::create_dir_all(dir.join("elsewhere")).unwrap();
+    ::std::os::unix::fs::symlink(dir.join("elsewhere"), out.join("dir")).unwrap();
+
+    assert_eq!(state.extract_current_entry(Some(&out), Some(Path::new("dir").join("b.txt"))), Err(Error::UnsafePath));
+    assert_eq!(state.extract_current_entry(None::<&Path>, Some(out.join("dir").join("b.txt"))), Err(Error::UnsafePath));
+    assert!(!dir.join("elsewhere").join("b.txt").exists());
+}
+
+#[test]
+fn escaped_names() {
+    let dir = prepare_dir("extract", "escaped_names");
+    let mut state = open(&dir, "<===> CON\n<===> trailing. \n<===> ...\n<===> a|b\n<===> nul.txt/aux\n");
+
+    let out = dir.join("out");
+    let mut extracted = vec![];
+    while let Some((_, _, name)) = state.next_entry() {
+        let name = name.to_string();
+        assert_eq!(state.test_current_entry(), Ok(()));
+        assert_eq!(state.extract_current_entry(Some(&out), Some(&name)), Ok(()));
+        extracted.push(name);
+    }
+    assert_eq!(extracted, vec!["%43ON", "trailing.%20", "..%2E", "a%7Cb", "%6Eul.txt", "%6Eul.txt/%61ux"]);
+
+    assert_eq!(files(&dir), vec![out.join("%43ON"), out.join("%6Eul.txt").join("%61ux"), out.join("..%2E"), out.join("a%7Cb"), out.join("trailing.%20")]);
+}
+
+#[test]
+fn directories() {
+    let dir = prepare_dir("extract", "directories");
+    let mut state = open(&dir, "<===> a/b/\n<===> c/d/e.txt\nE\n<===> f/\n");
+
+    let out = dir.join("out");
+    fs::create_dir_all(out.join("f")).unwrap();
+    while let Some((_, _, name)) = state.next_entry() {
+        let name = name.to_string();
+        assert_eq!(state.extract_current_entry(Some(&out), Some(&name)), Ok(()), "{}", name);
+    }
+
+    for dir in &[out.join("a"), out.join("a").join("b"), out.join("c"), out.join("c").join("d"), out.join("f")] {
+        assert!(dir.is_dir(), "{}", dir.display());
+    }
+    assert_eq!(files(&dir), vec![out.join("c").join("d").join("e.txt")]);
+    assert_eq!(fs::read_to_string(out.join("c").join("d").join("e.txt")).unwrap(), "E");
+}
+
+#[test]
+fn parents() {
+    let dir = prepare_dir("extract", "parents");
+    let mut state = open(&dir, ARCHIVE);
+    state.next_entry();
+    state.next_entry();
+    state.next_entry();
+
+    // Straight to the file, without the host creating dir/ first
+    let out = dir.join("out");
+    assert_eq!(state.extract_current_entry(Some(&out), Some(Path::new("dir").join("b.txt"))), Ok(()));
+    assert_eq!(fs::read_to_string(out.join("dir").join("b.txt")).unwrap(), "B\n");
+}
+
+#[test]
+fn directory_over_file() {
+    let dir = prepare_dir("extract", "directory_over_file");
+    let mut state = open(&dir, ARCHIVE);
+    state.next_entry();
+    state.next_entry();
+
+    let out = dir.join("out");
+    fs::create_dir_all(&out).unwrap();
+    fs::write(out.join("dir"), "File").unwrap();
+    assert_eq!(state.extract_current_entry(Some(&out), Some("dir")), Err(Error::Create));
+    assert_eq!(fs::read_to_string(out.join("dir")).unwrap(), "File");
+}
+
+
+/// Every file under the specified directory, besides `archive.hrx`, sorted
+fn files(dir: &Path) -> Vec<PathBuf> {
+    let mut ret = vec![];
+    for entry in fs::read_dir(dir).unwrap().map(Result::unwrap) {
+        if entry.file_type().unwrap().is_dir() {
+            ret.extend(files(&entry.path()));
+        } else if entry.file_name() != "archive.hrx" {
+            ret.push(entry.path());
+        }
+    }
+    ret.sort();
+    ret
+}
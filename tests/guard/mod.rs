@@ -0,0 +1,93 @@
+#[cfg(feature = "panic-injection")]
+use totalcmd_hrx::{CloseArchive, OpenArchiveW, ProcessFileW, ReadHeaderExW, PackFilesW, inject_panic};
+#[cfg(feature = "panic-injection")]
+use wcxhead::{self, tOpenArchiveDataW, tHeaderDataExW};
+#[cfg(feature = "panic-injection")]
+use self::super::util::wide;
+#[cfg(feature = "panic-injection")]
+use std::{iter, mem, ptr};
+use totalcmd_hrx::{set_panic_log, catch_panic};
+use self::super::util::prepare_dir;
+use std::fs;
+
+
+#[test]
+fn passthrough() {
+    assert_eq!(catch_panic(19, || 0), 0);
+    assert_eq!(catch_panic(Err(13), || Ok::<_, i32>("handle")), Ok("handle"));
+}
+
+#[test]
+fn panic_to_error() {
+    assert_eq!(catch_panic(19, || -> i32 { panic!("deliberate") }), 19);
+    assert_eq!(catch_panic(13, || vec![0][1]), 13);
+    assert_eq!(catch_panic(Err(13), || -> Result<(), _> { panic!("deliberate {}", "result") }), Err(13));
+}
+
+#[test]
+fn log() {
+    let dir = prepare_dir("guard", "log");
+    let log = dir.join("panic.log");
+
+    set_panic_log(Some(&log));
+    assert_eq!(catch_panic(17, || -> i32 { panic!("log test {}", "first") }), 17);
+    assert_eq!(catch_panic(19, || -> i32 { panic!("log test second") }), 19);
+    set_panic_log(None::<&str>);
+    assert_eq!(catch_panic(19, || -> i32 { panic!("not in the log") }), 19);
+
+    // Other tests panic concurrently
+    let logged = fs::read_to_string(&log).unwrap();
+    let lines = logged.lines().filter(|l| l.contains("log test") || l.contains("not in the log")).collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].ends_with(" panicked: log test first"));
+    assert!(lines[1].ends_with(" panicked: log test second"));
+}
+
+/// Run with `--features panic-injection`
+#[test]
+#[cfg(feature = "panic-injection")]
+fn exports() {
+    let dir = prepare_dir("guard", "exports");
+    fs::write(dir.join("archive.hrx"), "<===> a.txt\nA\n").unwrap();
+
+    let mut arc_name = wide(&dir.join("archive.hrx").to_string_lossy());
+    let mut data = tOpenArchiveDataW {
+        ArcName: arc_name.as_mut_ptr(),
+        OpenMode: wcxhead::PK_OM_EXTRACT,
+        OpenResult: 0,
+        CmtBuf: ptr::null_mut(),
+        CmtBufSize: 0,
+        CmtSize: 0,
+        CmtState: 0,
+    };
+
+    inject_panic("exports test open");
+    assert!(unsafe { OpenArchiveW(&mut data) }.is_null());
+    assert_eq!(data.OpenResult, wcxhead::E_BAD_ARCHIVE);
+
+    data.OpenResult = 0;
+    let handle = unsafe { OpenArchiveW(&mut data) };
+    assert!(!handle.is_null());
+    let mut header: tHeaderDataExW = unsafe { mem::zeroed() };
+    inject_panic("exports test header");
+    assert_eq!(unsafe { ReadHeaderExW(handle, &mut header) }, wcxhead::E_BAD_ARCHIVE);
+    assert_eq!(unsafe { ReadHeaderExW(handle, &mut header) }, 0);
+
+    let mut dest_name = wide(&dir.join("out").join("a.txt").to_string_lossy());
+    inject_panic("exports test test");
+    assert_eq!(unsafe { ProcessFileW(handle, wcxhead::PK_TEST, ptr::null_mut(), ptr::null_mut()) }, wcxhead::E_BAD_DATA);
+    inject_panic("exports test extract");
+    assert_eq!(unsafe { ProcessFileW(handle, wcxhead::PK_EXTRACT, ptr::null_mut(), dest_name.as_mut_ptr()) }, wcxhead::E_EWRITE);
+    assert!(!dir.join("out").join("a.txt").exists());
+    inject_panic("exports test close");
+    assert_eq!(CloseArchive(handle), wcxhead::E_ECLOSE);
+    assert_eq!(CloseArchive(handle), 0);
+
+    let mut packed_file = wide(&dir.join("packed.hrx").to_string_lossy());
+    let mut src_path = wide(&dir.to_string_lossy());
+    let mut add_list = wide("archive.hrx").into_iter().chain(iter::once(0)).collect::<Vec<_>>();
+    inject_panic("exports test pack");
+    assert_eq!(unsafe { PackFilesW(packed_file.as_mut_ptr(), ptr::null_mut(), src_path.as_mut_ptr(), add_list.as_mut_ptr(), 0) },
+               wcxhead::E_EWRITE);
+    assert!(!dir.join("packed.hrx").exists());
+}
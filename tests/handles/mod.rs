@@ -0,0 +1,98 @@
+use totalcmd_hrx::{SetProcessDataProc, StartMemPack, CloseArchive, OpenArchive, ProcessFile, DoneMemPack, ReadHeader, PackToMem};
+use wcxhead::{self, tOpenArchiveData, tHeaderData};
+use std::ffi::{CString, CStr};
+use libc::{c_char, c_int};
+use self::super::util::prepare_dir;
+use std::{fs, mem, ptr};
+
+
+static ARCHIVE: &str = "<===> file.txt\nContent\n";
+
+
+extern "system" fn progress(_: *mut c_char, _: c_int) -> c_int {
+    1
+}
+
+
+#[test]
+fn double_close() {
+    unsafe {
+        let (_name, mut data) = archive_data("double_close");
+        let handle = OpenArchive(&mut data);
+        assert!(!handle.is_null());
+
+        assert_eq!(CloseArchive(handle), 0);
+        assert_eq!(CloseArchive(handle), wcxhead::E_ECLOSE);
+    }
+}
+
+#[test]
+fn use_after_close() {
+    unsafe {
+        let (_name, mut data) = archive_data("use_after_close");
+        let handle = OpenArchive(&mut data);
+        let mut header: tHeaderData = mem::zeroed();
+        assert_eq!(ReadHeader(handle, &mut header), 0);
+        assert_eq!(CStr::from_ptr(header.FileName.as_ptr()).to_str(), Ok("file.txt"));
+
+        assert_eq!(CloseArchive(handle), 0);
+        assert_eq!(ReadHeader(handle, &mut header), wcxhead::E_BAD_ARCHIVE);
+        assert_eq!(ProcessFile(handle, wcxhead::PK_SKIP, ptr::null_mut(), ptr::null_mut()), wcxhead::E_BAD_ARCHIVE);
+        SetProcessDataProc(handle, progress);
+    }
+}
+
+#[test]
+fn foreign() {
+    unsafe {
+        let mut header: tHeaderData = mem::zeroed();
+        for &handle in &[0usize, 0xDEADBEEF] {
+            assert_eq!(ReadHeader(handle as _, &mut header), wcxhead::E_BAD_ARCHIVE);
+            assert_eq!(ProcessFile(handle as _, wcxhead::PK_SKIP, ptr::null_mut(), ptr::null_mut()), wcxhead::E_BAD_ARCHIVE);
+            assert_eq!(CloseArchive(handle as _), wcxhead::E_ECLOSE);
+            assert_eq!(DoneMemPack(handle as _), wcxhead::E_ECLOSE);
+        }
+    }
+}
+
+#[test]
+fn mixed_up() {
+    unsafe {
+        let (_name, mut data) = archive_data("mixed_up");
+        let archive = OpenArchive(&mut data);
+        let file_name = CString::new("file.txt").unwrap();
+        let mempack = StartMemPack(0, file_name.as_ptr() as *mut _);
+        assert!(!mempack.is_null());
+
+        let mut header: tHeaderData = mem::zeroed();
+        assert_eq!(ReadHeader(mempack, &mut header), wcxhead::E_BAD_ARCHIVE);
+        assert_eq!(CloseArchive(mempack), wcxhead::E_ECLOSE);
+
+        let (mut taken, mut written) = (0, 0);
+        assert_eq!(PackToMem(archive, ptr::null_mut(), 0, &mut taken, ptr::null_mut(), 0, &mut written, 0),
+                   wcxhead::E_BAD_ARCHIVE);
+        assert_eq!(DoneMemPack(archive), wcxhead::E_ECLOSE);
+
+        assert_eq!(DoneMemPack(mempack), 0);
+        assert_eq!(DoneMemPack(mempack), wcxhead::E_ECLOSE);
+        assert_eq!(CloseArchive(archive), 0);
+    }
+}
+
+
+fn archive_data(test_name: &str) -> (CString, tOpenArchiveData) {
+    let dir = prepare_dir("handles", test_name);
+    fs::write(dir.join("archive.hrx"), ARCHIVE).unwrap();
+
+    let name = CString::new(dir.join("archive.hrx").to_str().unwrap()).unwrap();
+    let data = tOpenArchiveData {
+        ArcName: name.as_ptr() as *mut _,
+        OpenMode: wcxhead::PK_OM_LIST,
+        OpenResult: 0,
+        CmtBuf: ptr::null_mut(),
+        CmtBufSize: 0,
+        CmtSize: 0,
+        CmtState: 0,
+    };
+    (name, data)
+}
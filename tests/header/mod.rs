@@ -0,0 +1,89 @@
+use wcxhead::{tHeaderDataExW, tHeaderDataEx, tHeaderData};
+use totalcmd_hrx::{EntryHeader, FillHeader};
+use std::mem;
+
+
+const SIZES: [(u64, (u32, u32), i32); 8] = [(0, (0, 0), 0),
+                                            ((1 << 24) - 1, (0x00FF_FFFF, 0), 0x00FF_FFFF),
+                                            (1 << 24, (0x0100_0000, 0), 0x0100_0000),
+                                            ((1 << 24) + 1, (0x0100_0001, 0), 0x0100_0001),
+                                            ((1 << 31) - 1, (0x7FFF_FFFF, 0), i32::MAX),
+                                            (1 << 31, (0x8000_0000, 0), i32::MAX),
+                                            ((1 << 32) - 1, (0xFFFF_FFFF, 0), i32::MAX),
+                                            (1 << 32, (0, 1), i32::MAX)];
+
+
+#[test]
+fn ansi() {
+    for &(size, _, saturated) in &SIZES {
+        let mut header: tHeaderData = unsafe { mem::zeroed() };
+        header.fill_header(&entry(size));
+
+        assert_eq!((header.PackSize, header.UnpSize), (saturated, saturated), "{}", size);
+        assert_eq!(header.FileCRC as u32, 0xCBF43926);
+        assert_eq!(header.FileName[..13].iter().map(|&c| c as u8).collect::<Vec<_>>(), b"dir\\file.txt\0");
+    }
+}
+
+#[test]
+fn ex() {
+    for &(size, (low, high), _) in &SIZES {
+        let mut header: tHeaderDataEx = unsafe { mem::zeroed() };
+        header.fill_header(&entry(size));
+
+        assert_eq!((header.PackSize, header.PackSizeHigh), (low, high), "{}", size);
+        assert_eq!((header.UnpSize, header.UnpSizeHigh), (low, high), "{}", size);
+    }
+}
+
+#[test]
+fn ex_wide() {
+    for &(size, (low, high), _) in &SIZES {
+        let mut header: tHeaderDataExW = unsafe { mem::zeroed() };
+        header.fill_header(&entry(size));
+
+        assert_eq!((header.PackSize, header.PackSizeHigh), (low, high), "{}", size);
+        assert_eq!((header.UnpSize, header.UnpSizeHigh), (low, high), "{}", size);
+        assert_eq!(&header.FileName[..13], &"dir\\file.txt\0".encode_utf16().collect::<Vec<_>>()[..]);
+    }
+}
+
+#[test]
+fn sizes_differ() {
+    let entry = EntryHeader { packed_size: 40, ..entry(30) };
+
+    let mut header: tHeaderData = unsafe { mem::zeroed() };
+    header.fill_header(&entry);
+    assert_eq!((header.PackSize, header.UnpSize), (40, 30));
+
+    let mut header: tHeaderDataEx = unsafe { mem::zeroed() };
+    header.fill_header(&entry);
+    assert_eq!((header.PackSize, header.PackSizeHigh, header.UnpSize, header.UnpSizeHigh), (40, 0, 30, 0));
+
+    let mut header: tHeaderDataExW = unsafe { mem::zeroed() };
+    header.fill_header(&entry);
+    assert_eq!((header.PackSize, header.PackSizeHigh, header.UnpSize, header.UnpSizeHigh), (40, 0, 30, 0));
+}
+
+#[test]
+fn name_truncated() {
+    let name = "a".repeat(2000);
+    let mut header: tHeaderDataEx = unsafe { mem::zeroed() };
+    header.FileName = [1; 1024];
+    header.fill_header(&EntryHeader { name: &name, ..entry(0) });
+
+    assert!(header.FileName[..1023].iter().all(|&c| c == b'a' as _));
+    assert_eq!(header.FileName[1023], 0);
+}
+
+
+fn entry(size: u64) -> EntryHeader<'static> {
+    EntryHeader {
+        name: "dir\\file.txt",
+        packed_size: size,
+        size: size,
+        file_time: 0,
+        attr: 0x00,
+        crc: 0xCBF43926,
+    }
+}
@@ -0,0 +1,200 @@
+use totalcmd_hrx::{SetProcessDataProcW, CloseArchive, DeleteFilesW, OpenArchiveW, ProcessFileW, ReadHeaderExW, PackFilesW};
+use wcxhead::{self, tOpenArchiveDataW, tHeaderDataExW};
+use totalcmd_hrx::util::wide_to_string;
+use self::super::super::util::wide;
+use std::cell::{RefCell, Cell};
+use std::path::{PathBuf, Path};
+use std::{iter, mem, ptr, fs};
+use libc::c_int;
+
+
+thread_local! {
+    /// Progress reports received on this thread since the last operation finished
+    static PROGRESS: RefCell<Vec<(String, c_int)>> = const { RefCell::new(Vec::new()) };
+    /// How many more progress reports to accept before aborting
+    static ABORT_AFTER: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+
+extern "system" fn process_data(name: *mut u16, size: c_int) -> c_int {
+    let name = unsafe { wide_to_string(name) };
+    PROGRESS.with(|p| p.borrow_mut().push((name, size)));
+
+    match ABORT_AFTER.with(Cell::get) {
+        Some(0) => 0,
+        Some(left) => {
+            ABORT_AFTER.with(|a| a.set(Some(left - 1)));
+            1
+        }
+        None => 1,
+    }
+}
+
+
+/// An entry as listed by [`ReadHeaderExW`]
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub struct Listed {
+    pub name: String,
+    pub size: u64,
+    pub time: c_int,
+    pub attr: c_int,
+    pub crc: u32,
+}
+
+impl Listed {
+    pub fn is_dir(&self) -> bool {
+        (self.attr & 0x10) != 0
+    }
+}
+
+impl<'h> From<&'h tHeaderDataExW> for Listed {
+    fn from(header: &'h tHeaderDataExW) -> Listed {
+        let name_len = header.FileName.iter().position(|&c| c == 0).unwrap_or(header.FileName.len());
+        Listed {
+            name: String::from_utf16_lossy(&header.FileName[..name_len]),
+            size: ((header.UnpSizeHigh as u64) << 32) | header.UnpSize as u64,
+            time: header.FileTime,
+            attr: header.FileAttr,
+            crc: header.FileCRC as u32,
+        }
+    }
+}
+
+
+/// Drives the wide exports the way Total Commander does, on the current thread
+///
+/// Every export called is recorded in `calls` along with what it returned, and every progress report in `progress`.
+#[derive(Debug, Default)]
+pub struct Host {
+    pub calls: Vec<(&'static str, c_int)>,
+    pub progress: Vec<(String, c_int)>,
+}
+
+impl Host {
+    pub fn new() -> Host {
+        PROGRESS.with(|p| p.borrow_mut().clear());
+        ABORT_AFTER.with(|a| a.set(None));
+        Host::default()
+    }
+
+    /// Have the user abort the operation after `reports` more progress reports
+    pub fn abort_after(&mut self, reports: usize) {
+        ABORT_AFTER.with(|a| a.set(Some(reports)));
+    }
+
+    /// Open `archive` for listing, skipping every entry
+    pub fn list(&mut self, archive: &Path) -> Result<Vec<Listed>, c_int> {
+        self.walk(archive, wcxhead::PK_OM_LIST, |_| (wcxhead::PK_SKIP, None))
+    }
+
+    /// Test every file in `archive`
+    pub fn test(&mut self, archive: &Path) -> Result<Vec<Listed>, c_int> {
+        self.walk(archive,
+                  wcxhead::PK_OM_EXTRACT,
+                  |entry| if entry.is_dir() {
+                      (wcxhead::PK_SKIP, None)
+                  } else {
+                      (wcxhead::PK_TEST, None)
+                  })
+    }
+
+    /// Extract all of `archive` under `dest`, creating the listed directories as the host does
+    pub fn extract(&mut self, archive: &Path, dest: &Path) -> Result<Vec<Listed>, c_int> {
+        self.walk(archive,
+                  wcxhead::PK_OM_EXTRACT,
+                  |entry| if entry.is_dir() {
+                      fs::create_dir_all(dest.join(&entry.name)).unwrap();
+                      (wcxhead::PK_SKIP, None)
+                  } else {
+                      (wcxhead::PK_EXTRACT, Some(dest.join(&entry.name)))
+                  })
+    }
+
+    /// Pack `files`, relative to `source`, into `archive`
+    pub fn pack(&mut self, archive: &Path, sub_path: Option<&str>, source: &Path, files: &[&str], flags: c_int) -> c_int {
+        let mut archive = wide(&archive.to_string_lossy());
+        let mut sub_path = sub_path.map(wide);
+        let mut source = wide(&source.to_string_lossy());
+        let mut add_list = wide_list(files);
+
+        // INVALID_HANDLE_VALUE
+        SetProcessDataProcW(usize::MAX as _, process_data);
+        let result = unsafe {
+            PackFilesW(archive.as_mut_ptr(),
+                       sub_path.as_mut().map(|s| s.as_mut_ptr()).unwrap_or(ptr::null_mut()),
+                       source.as_mut_ptr(),
+                       add_list.as_mut_ptr(),
+                       flags)
+        };
+        self.finish("PackFilesW", result)
+    }
+
+    /// Delete `names`, which may be wildcards, from `archive`
+    pub fn delete(&mut self, archive: &Path, names: &[&str]) -> c_int {
+        let mut archive = wide(&archive.to_string_lossy());
+        let mut delete_list = wide_list(names);
+
+        SetProcessDataProcW(usize::MAX as _, process_data);
+        let result = unsafe { DeleteFilesW(archive.as_mut_ptr(), delete_list.as_mut_ptr()) };
+        self.finish("DeleteFilesW", result)
+    }
+
+    /// Open `archive` in `mode`, then read every header, handling each entry as `process` returns, until the end or an error
+    fn walk<F: FnMut(&Listed) -> (c_int, Option<PathBuf>)>(&mut self, archive: &Path, mode: c_int, mut process: F) -> Result<Vec<Listed>, c_int> {
+        let mut arc_name = wide(&archive.to_string_lossy());
+        let mut data = tOpenArchiveDataW {
+            ArcName: arc_name.as_mut_ptr(),
+            OpenMode: mode,
+            OpenResult: 0,
+            CmtBuf: ptr::null_mut(),
+            CmtBufSize: 0,
+            CmtSize: 0,
+            CmtState: 0,
+        };
+        let handle = unsafe { OpenArchiveW(&mut data) };
+        self.finish("OpenArchiveW", data.OpenResult);
+        if handle.is_null() {
+            return Err(data.OpenResult);
+        }
+        SetProcessDataProcW(handle, process_data);
+
+        let mut listed = vec![];
+        let mut result = Ok(());
+        loop {
+            let mut header: tHeaderDataExW = unsafe { mem::zeroed() };
+            match self.finish("ReadHeaderExW", unsafe { ReadHeaderExW(handle, &mut header) }) {
+                0 => {}
+                wcxhead::E_END_ARCHIVE => break,
+                err => {
+                    result = Err(err);
+                    break;
+                }
+            }
+
+            let entry = Listed::from(&header);
+            let (operation, dest_name) = process(&entry);
+            let mut dest_name = dest_name.map(|n| wide(&n.to_string_lossy()));
+            let processed = unsafe { ProcessFileW(handle, operation, ptr::null_mut(), dest_name.as_mut().map(|n| n.as_mut_ptr()).unwrap_or(ptr::null_mut())) };
+            listed.push(entry);
+            if self.finish("ProcessFileW", processed) != 0 {
+                result = Err(processed);
+                break;
+            }
+        }
+
+        self.finish("CloseArchive", CloseArchive(handle));
+        result.map(|()| listed)
+    }
+
+    fn finish(&mut self, export: &'static str, result: c_int) -> c_int {
+        self.calls.push((export, result));
+        PROGRESS.with(|p| self.progress.append(&mut p.borrow_mut()));
+        result
+    }
+}
+
+
+/// As [`CListIter`](../../totalcmd_hrx/util/struct.CListIter.html) expects it, with an extra NUL at the end
+fn wide_list(elems: &[&str]) -> Vec<u16> {
+    elems.iter().flat_map(|e| wide(e)).chain(iter::once(0)).collect()
+}
@@ -0,0 +1,419 @@
+mod harness;
+
+use self::harness::{Listed, Host};
+use self::super::util;
+use totalcmd_hrx::{E_FILES_SKIPPED, ProgressCallbacks, PackFlags, Config, pack_archive};
+use totalcmd_hrx::util::{HOST_PATH_SEPARATOR, system_time_to_totalcmd_time};
+use std::time::{SystemTime, Duration};
+use std::fs::{self, File};
+use std::path::PathBuf;
+use libc::c_int;
+use wcxhead;
+
+
+#[test]
+fn create() {
+    let dir = prepare_dir("create");
+    let archive = dir.join("archive.hrx");
+    let nested = format!("dir{}b.txt", HOST_PATH_SEPARATOR);
+
+    let mut host = Host::new();
+    assert_eq!(host.pack(&archive, None, &dir.join("src"), &["a.txt", &format!("dir{}", HOST_PATH_SEPARATOR), &nested], wcxhead::PK_PACK_SAVE_PATHS),
+               0);
+    assert_eq!(host.calls, vec![("PackFilesW", 0)]);
+    assert_eq!(host.progress.iter().filter(|&&(_, size)| size > 0).map(|&(_, size)| size).sum::<i32>(), 4);
+    assert_eq!(host.progress.last(), Some(&(archive.to_string_lossy().into_owned(), -100)));
+
+    assert_eq!(names(host.list(&archive)), vec!["a.txt".to_string(), "dir".to_string(), nested]);
+    assert_eq!(&host.calls[1..],
+               &[("OpenArchiveW", 0),
+                 ("ReadHeaderExW", 0),
+                 ("ProcessFileW", 0),
+                 ("ReadHeaderExW", 0),
+                 ("ProcessFileW", 0),
+                 ("ReadHeaderExW", 0),
+                 ("ProcessFileW", 0),
+                 ("ReadHeaderExW", wcxhead::E_END_ARCHIVE),
+                 ("CloseArchive", 0)]);
+}
+
+#[test]
+fn add() {
+    let dir = prepare_dir("add");
+    let archive = dir.join("archive.hrx");
+    fs::write(&archive, "<===> old.txt\nOld\n").unwrap();
+
+    let mut host = Host::new();
+    assert_eq!(host.pack(&archive, Some("sub"), &dir.join("src"), &["a.txt"], 0), 0);
+
+    let listed = host.list(&archive).unwrap();
+    assert_eq!(names(Ok(listed.clone())), vec!["old.txt".to_string(), "sub".to_string(), format!("sub{}a.txt", HOST_PATH_SEPARATOR)]);
+    assert!(listed[1].is_dir());
+    assert_eq!(listed[2].size, 1);
+    assert_eq!(listed[2].crc, 0xD3D99E8B);
+}
+
+#[test]
+fn skipped() {
+    let dir = prepare_dir("skipped");
+    let archive = dir.join("archive.hrx");
+    fs::write(dir.join("src").join("binary.bin"), b"\xFF").unwrap();
+
+    let mut host = Host::new();
+    assert_eq!(host.pack(&archive, None, &dir.join("src"), &["a.txt", "binary.bin"], wcxhead::PK_PACK_MOVE_FILES), E_FILES_SKIPPED);
+    assert_eq!(names(host.list(&archive)), vec!["a.txt".to_string()]);
+    assert!(!dir.join("src").join("a.txt").exists());
+    assert!(dir.join("src").join("binary.bin").exists());
+}
+
+#[test]
+fn create_empty() {
+    let dir = prepare_dir("create_empty");
+    let archive = dir.join("archive.hrx");
+
+    let mut host = Host::new();
+    assert_eq!(host.pack(&archive, None, &dir.join("src"), &[&format!("dir{}", HOST_PATH_SEPARATOR)], 0), wcxhead::E_NO_FILES);
+    assert!(!archive.exists());
+}
+
+#[test]
+fn file_as_directory() {
+    let dir = prepare_dir("file_as_directory");
+    let archive = dir.join("archive.hrx");
+    fs::write(&archive, "<===> a.txt\nA\n<===> big.txt/b.txt\nB\n").unwrap();
+
+    let mut host = Host::new();
+    assert_eq!(host.pack(&archive, Some("a.txt"), &dir.join("src"), &["big.txt"], 0), wcxhead::E_ECREATE);
+    assert_eq!(host.pack(&archive, None, &dir.join("src"), &["big.txt"], 0), wcxhead::E_ECREATE);
+    assert_eq!(fs::read_to_string(&archive).unwrap(), "<===> a.txt\nA\n<===> big.txt/b.txt\nB\n");
+}
+
+#[test]
+fn implied_directory() {
+    let dir = prepare_dir("implied_directory");
+    let archive = dir.join("archive.hrx");
+    fs::write(&archive, "<===> dir/b.txt\nB\n").unwrap();
+
+    let mut host = Host::new();
+    assert_eq!(host.pack(&archive, None, &dir.join("src"), &[&format!("dir{}", HOST_PATH_SEPARATOR)], wcxhead::PK_PACK_SAVE_PATHS),
+               0);
+    assert_eq!(names(host.list(&archive)), vec!["dir".to_string(), format!("dir{}b.txt", HOST_PATH_SEPARATOR)]);
+}
+
+#[test]
+fn virtual_directories() {
+    let dir = prepare_dir("virtual_directories");
+    let archive = dir.join("archive.hrx");
+    let contents = "<===> dir/sub/file.txt\nF\n<===> dir/other.txt\nO\n<===> top.txt\nT\n<===> dir/sub/more.txt\nM\n<===> x/\n<===> x/y/z.txt\n";
+    fs::write(&archive, contents).unwrap();
+
+    let mut host = Host::new();
+    let listed = host.list(&archive).unwrap();
+    assert_eq!(listed.iter().map(|l| (l.name.clone(), l.is_dir())).collect::<Vec<_>>(),
+               vec![(host_path("dir"), true),
+                    (host_path("dir/sub"), true),
+                    (host_path("dir/sub/file.txt"), false),
+                    (host_path("dir/other.txt"), false),
+                    (host_path("top.txt"), false),
+                    (host_path("dir/sub/more.txt"), false),
+                    (host_path("x"), true),
+                    (host_path("x/y"), true),
+                    (host_path("x/y/z.txt"), false)]);
+
+    let out = dir.join("out");
+    assert_eq!(host.extract(&archive, &out), Ok(listed));
+    assert!(out.join("x").join("y").is_dir());
+    assert_eq!(fs::read_to_string(&archive).unwrap(), contents);
+
+    assert_eq!(host.delete(&archive, &[&host_path("dir/sub/*.*"), &host_path("x/y/*.*")]), 0);
+    assert_eq!(fs::read_to_string(&archive).unwrap(), "<===> dir/other.txt\nO\n<===> top.txt\nT\n<===> x/\n");
+    assert_eq!(names(host.list(&archive)), vec![host_path("dir"), host_path("dir/other.txt"), host_path("top.txt"), host_path("x")]);
+}
+
+#[test]
+fn overwrite() {
+    let dir = prepare_dir("overwrite");
+    let archive = dir.join("archive.hrx");
+    fs::write(&archive, "<===> a.txt\nOld contents\n").unwrap();
+
+    let mut host = Host::new();
+    assert_eq!(host.pack(&archive, None, &dir.join("src"), &["a.txt"], 0), 0);
+    assert_eq!(host.extract(&archive, &dir.join("out")).map(|l| l.len()), Ok(1));
+    assert_eq!(fs::read_to_string(dir.join("out").join("a.txt")).unwrap(), "A");
+}
+
+#[test]
+fn delete() {
+    let dir = prepare_dir("delete");
+    let archive = dir.join("archive.hrx");
+    fs::write(&archive, "<===> a.txt\nA\n<===> dir/\n<===> dir/b.txt\nB\n<===> c.txt\nC\n").unwrap();
+
+    let mut host = Host::new();
+    assert_eq!(host.delete(&archive, &["a.txt", &format!("dir{}*.*", HOST_PATH_SEPARATOR)]), 0);
+    assert_eq!(host.calls, vec![("DeleteFilesW", 0)]);
+    assert_eq!(host.progress.last(), Some(&(archive.to_string_lossy().into_owned(), -1100)));
+
+    assert_eq!(names(host.list(&archive)), vec!["c.txt".to_string()]);
+}
+
+#[test]
+fn delete_all() {
+    let dir = prepare_dir("delete_all");
+    let archive = dir.join("archive.hrx");
+    fs::write(&archive, "<===> a.txt\nA\n<===> dir/\n<===> dir/b.txt\nB\n").unwrap();
+
+    let mut host = Host::new();
+    assert_eq!(host.delete(&archive, &["a.txt", &format!("dir{}*.*", HOST_PATH_SEPARATOR)]), 0);
+    assert!(!archive.exists());
+    assert_eq!(host.list(&archive), Err(wcxhead::E_EOPEN));
+}
+
+#[test]
+fn delete_wildcards() {
+    let dir = prepare_dir("delete_wildcards");
+    let archive = dir.join("archive.hrx");
+    let contents = "<===> a.txt\nA\n<===> ab.txt\nAB\n<===> Makefile\nM\n<===> dir/\n<===> dir/sub/c.txt\nC\n<===> dir/d.md\nD\n<===> keep/e.txt\nE\n";
+    fs::write(&archive, contents).unwrap();
+
+    let mut host = Host::new();
+    assert_eq!(host.delete(&archive, &["nothing*"]), wcxhead::E_NO_FILES);
+    assert_eq!(fs::read_to_string(&archive).unwrap(), contents);
+
+    host.progress.clear();
+    assert_eq!(host.delete(&archive, &["?.txt", "M*", &host_path("dir/*.*")]), 0);
+    assert_eq!(host.progress.iter().filter(|&&(_, size)| size == -100).map(|(name, _)| name.clone()).collect::<Vec<_>>(),
+               vec![host_path("a.txt"), host_path("Makefile"), host_path("dir"), host_path("dir/sub/c.txt"), host_path("dir/d.md")]);
+    assert_eq!(fs::read_to_string(&archive).unwrap(), "<===> ab.txt\nAB\n<===> keep/e.txt\nE\n");
+}
+
+#[test]
+fn delete_with_implied_directory() {
+    let dir = prepare_dir("delete_with_implied_directory");
+    let archive = dir.join("archive.hrx");
+    fs::write(&archive, "<===> dir/a.txt\nA\n<===> dir/c.txt\nC\n<===> b.txt\nB\n").unwrap();
+
+    let mut host = Host::new();
+    assert_eq!(host.delete(&archive, &[&host_path("dir/a.txt"), &host_path("dir/*.*")]), 0);
+    assert_eq!(fs::read_to_string(&archive).unwrap(), "<===> b.txt\nB\n");
+    assert_eq!(host.progress.iter().filter(|&&(_, size)| size == -100).map(|(name, _)| name.clone()).collect::<Vec<_>>(),
+               vec![host_path("dir/a.txt"), host_path("dir/c.txt")]);
+
+    fs::write(&archive, "<===> dir/a.txt\nA\n<===> b.txt\nB\n").unwrap();
+    assert_eq!(host.delete(&archive, &[&host_path("dir/a.txt"), &host_path("dir/*.*")]), 0);
+    assert_eq!(fs::read_to_string(&archive).unwrap(), "<===> b.txt\nB\n");
+}
+
+#[test]
+fn delete_escaped() {
+    let dir = prepare_dir("delete_escaped");
+    let archive = dir.join("archive.hrx");
+    fs::write(&archive, "<===> a?.txt\n?\n<===> ab.txt\nAB\n<===> README.md\n<===> readme.md\n").unwrap();
+
+    let mut host = Host::new();
+    assert_eq!(host.delete(&archive, &["a%3F.txt", "readme%~1.md"]), 0);
+    assert_eq!(names(host.list(&archive)), vec!["ab.txt".to_string(), "README.md".to_string()]);
+}
+
+#[test]
+fn literal_percent() {
+    let dir = prepare_dir("literal_percent");
+    let archive = dir.join("archive.hrx");
+    fs::write(dir.join("src").join("100%41.txt"), "A").unwrap();
+    fs::write(dir.join("src").join("a%2Fb.txt"), "B").unwrap();
+
+    let mut host = Host::new();
+    assert_eq!(host.pack(&archive, None, &dir.join("src"), &["100%41.txt", "a%2Fb.txt"], 0), 0);
+    assert_eq!(fs::read_to_string(&archive).unwrap(), "<===> 100%41.txt\nA\n<===> a%2Fb.txt\nB");
+    assert_eq!(names(host.list(&archive)), vec!["100%2541.txt".to_string(), "a%252Fb.txt".to_string()]);
+
+    assert_eq!(host.delete(&archive, &["100%2541.txt"]), 0);
+    assert_eq!(fs::read_to_string(&archive).unwrap(), "<===> a%2Fb.txt\nB");
+}
+
+#[test]
+fn escaped_round_trip() {
+    let dir = prepare_dir("escaped_round_trip");
+    let archive = dir.join("archive.hrx");
+    let contents = "<===> CON\nC\n<===> what?.txt\nW\n<===> trailing. \nT\n<===> 100%41.txt\nP\n<===> README.md\nR\n<===> readme.md\nr\n<===> sub/nul.txt\nN";
+    fs::write(&archive, contents).unwrap();
+
+    let mut host = Host::new();
+    let out = dir.join("out");
+    let listed = names(host.extract(&archive, &out));
+    let files = listed.iter().filter(|name| *name != "sub").map(|name| &name[..]).collect::<Vec<_>>();
+
+    let repacked = dir.join("repacked.hrx");
+    assert_eq!(host.pack(&repacked, None, &out, &files, wcxhead::PK_PACK_SAVE_PATHS), 0);
+    assert_eq!(fs::read_to_string(&repacked).unwrap(), contents);
+}
+
+#[test]
+fn extract() {
+    let dir = prepare_dir("extract");
+    let archive = dir.join("archive.hrx");
+    fs::write(&archive, "<===> a.txt\nA\n<===> dir/\n<===> dir/b.txt\nBee\n").unwrap();
+
+    let mut host = Host::new();
+    let listed = host.test(&archive).unwrap();
+    assert!(listed.iter().all(|l| !l.is_dir() || l.size == 0));
+    assert!(host.calls.iter().all(|&(_, result)| result == 0 || result == wcxhead::E_END_ARCHIVE));
+
+    let out = dir.join("out");
+    assert_eq!(host.extract(&archive, &out), Ok(listed));
+    assert_eq!(fs::read_to_string(out.join("a.txt")).unwrap(), "A");
+    assert_eq!(fs::read_to_string(out.join("dir").join("b.txt")).unwrap(), "Bee\n");
+
+    let extracted = host.progress.iter().filter(|(name, _)| name.starts_with(&*out.to_string_lossy())).map(|&(_, size)| size).collect::<Vec<_>>();
+    assert_eq!(extracted, vec![1, 4]);
+}
+
+#[test]
+fn extracted_sizes() {
+    let dir = prepare_dir("extracted_sizes");
+    let archive = dir.join("archive.hrx");
+    fs::write(&archive,
+              "<===>\nencoding: base64\n<===> binary.bin\nMTIzNDU2Nzg5\n<===>\nline-endings: crlf\n<===> crlf.txt\na\nb\n<===>\nencoding: base64\n\
+               <===> last.txt\n")
+        .unwrap();
+
+    let mut host = Host::new();
+    let listed = host.list(&archive).unwrap();
+    assert_eq!(listed.iter().map(|l| (&l.name[..], l.size)).collect::<Vec<_>>(),
+               vec![("binary.bin", 9), ("crlf.txt", 4), ("last.txt", 0)]);
+
+    host.extract(&archive, &dir.join("out")).unwrap();
+    for entry in &listed {
+        assert_eq!(fs::metadata(dir.join("out").join(&entry.name)).unwrap().len(), entry.size, "{}", entry.name);
+    }
+}
+
+#[test]
+fn test_bad_body() {
+    let dir = prepare_dir("test_bad_body");
+    let archive = dir.join("archive.hrx");
+    fs::write(&archive, "<===> a.txt\nA\n<===>\nencoding: base64\n<===> dir/bad.bin\nMTIzNDU2Nzg\n<===> c.txt\nC\n").unwrap();
+
+    let mut host = Host::new();
+    assert_eq!(host.test(&archive), Err(wcxhead::E_BAD_DATA));
+    assert_eq!(host.calls[host.calls.len() - 2..], [("ProcessFileW", wcxhead::E_BAD_DATA), ("CloseArchive", 0)]);
+    assert_eq!(host.progress, vec![]);
+}
+
+#[test]
+fn test_boundary_in_body() {
+    let dir = prepare_dir("test_boundary_in_body");
+    let archive = dir.join("archive.hrx");
+    fs::write(&archive, "<===> a.txt\n<===>A\n<===> c.txt\nC\n").unwrap();
+
+    let mut host = Host::new();
+    assert_eq!(host.test(&archive), Err(wcxhead::E_BAD_ARCHIVE));
+    assert_eq!(host.calls, vec![("OpenArchiveW", wcxhead::E_BAD_ARCHIVE)]);
+}
+
+#[test]
+fn timestamps() {
+    let dir = prepare_dir("timestamps");
+    let archive = dir.join("archive.hrx");
+    let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1714564800);
+    let archive_mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1577836800);
+    File::options().write(true).open(dir.join("src").join("a.txt")).unwrap().set_modified(mtime).unwrap();
+
+    for &(timestamps_in_comments, packed) in &[(false, "<===> a.txt\nA"), (true, "<===>\nmtime: 2024-05-01T12:00:00Z\n<===> a.txt\nA")] {
+        let _ = fs::remove_file(&archive);
+        assert_eq!(pack_archive(&archive,
+                                None::<&str>,
+                                dir.join("src"),
+                                ["a.txt"].iter(),
+                                PackFlags::default(),
+                                &Config { timestamps_in_comments: timestamps_in_comments, ..Config::default() },
+                                &ProgressCallbacks::default()),
+                   Ok(vec![]));
+        assert_eq!(fs::read_to_string(&archive).unwrap(), packed);
+    }
+
+    fs::write(&archive, "<===>\nmtime: 2024-05-01T12:00:00Z\n<===> a.txt\nA\n<===> b.txt\nB\n").unwrap();
+    File::options().write(true).open(&archive).unwrap().set_modified(archive_mtime).unwrap();
+
+    let mut host = Host::new();
+    let out = dir.join("out");
+    let listed = host.extract(&archive, &out).unwrap();
+    assert_eq!(listed.iter().map(|l| l.time).collect::<Vec<_>>(),
+               vec![system_time_to_totalcmd_time(&mtime), system_time_to_totalcmd_time(&archive_mtime)]);
+    assert_eq!(fs::metadata(out.join("a.txt")).unwrap().modified().unwrap(), mtime);
+    assert!(fs::metadata(out.join("b.txt")).unwrap().modified().unwrap() > archive_mtime);
+}
+
+#[test]
+fn abort_pack() {
+    let dir = prepare_dir("abort_pack");
+    let archive = dir.join("archive.hrx");
+
+    let mut host = Host::new();
+    host.abort_after(0);
+    assert_eq!(host.pack(&archive, None, &dir.join("src"), &["a.txt", "big.txt"], 0), wcxhead::E_EABORTED);
+    assert_eq!(host.progress.len(), 1);
+    assert!(!archive.exists());
+}
+
+#[test]
+fn move_unwritten() {
+    let dir = prepare_dir("move_unwritten");
+    let archive = dir.join("archive.hrx");
+
+    let mut host = Host::new();
+    host.abort_after(1);
+    assert_eq!(host.pack(&archive, None, &dir.join("src"), &["a.txt", "big.txt"], wcxhead::PK_PACK_MOVE_FILES), wcxhead::E_EABORTED);
+    assert!(!archive.exists());
+
+    let mut host = Host::new();
+    assert_ne!(host.pack(&dir.join("missing").join("archive.hrx"), None, &dir.join("src"), &["a.txt"], wcxhead::PK_PACK_MOVE_FILES), 0);
+
+    assert!(dir.join("src").join("a.txt").exists());
+    assert!(dir.join("src").join("big.txt").exists());
+}
+
+#[test]
+fn abort_extract() {
+    let dir = prepare_dir("abort_extract");
+    let archive = dir.join("archive.hrx");
+    fs::write(&archive, "<===> a.txt\nA\n<===> c.txt\nC\n").unwrap();
+
+    let mut host = Host::new();
+    host.abort_after(1);
+    let out = dir.join("out");
+    assert_eq!(host.extract(&archive, &out), Err(wcxhead::E_EABORTED));
+    assert_eq!(host.calls[host.calls.len() - 2..], [("ProcessFileW", wcxhead::E_EABORTED), ("CloseArchive", 0)]);
+
+    assert!(out.join("a.txt").exists());
+    assert!(!out.join("c.txt").exists());
+}
+
+#[test]
+fn missing() {
+    let dir = prepare_dir("missing");
+
+    let mut host = Host::new();
+    assert_eq!(host.list(&dir.join("archive.hrx")), Err(wcxhead::E_EOPEN));
+    assert_eq!(host.calls, vec![("OpenArchiveW", wcxhead::E_EOPEN)]);
+}
+
+
+fn names(listed: Result<Vec<Listed>, c_int>) -> Vec<String> {
+    listed.unwrap().into_iter().map(|l| l.name).collect()
+}
+
+/// The specified `/`-separated path with the host's separators
+fn host_path(path: &str) -> String {
+    path.replace('/', &HOST_PATH_SEPARATOR.to_string())
+}
+
+/// With `src/a.txt`, `src/big.txt`, and `src/dir/b.txt` to pack
+fn prepare_dir(test_name: &str) -> PathBuf {
+    let dir = util::prepare_dir("host", test_name);
+    fs::create_dir_all(dir.join("src").join("dir")).unwrap();
+
+    fs::write(dir.join("src").join("a.txt"), "A").unwrap();
+    fs::write(dir.join("src").join("big.txt"), "B".repeat(1000)).unwrap();
+    fs::write(dir.join("src").join("dir").join("b.txt"), "BBB").unwrap();
+    dir
+}
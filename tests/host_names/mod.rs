@@ -0,0 +1,48 @@
+use totalcmd_hrx::util::unescape_entry_path;
+use totalcmd_hrx::host_names;
+
+
+static PATHS: &[&str] = &["README.md", "readme.md", "Dir", "dir/file.txt", "DIR/file.txt", "CON/aux.txt", "what?.txt", "trailing. ", "README%~1.md",
+                          "100%41.txt", "a%2Fb.txt"];
+
+
+#[test]
+fn escaped() {
+    let names = host_names(PATHS.iter().cloned());
+
+    assert_eq!(names["README.md"], "README.md");
+    assert_eq!(names["CON/aux.txt"], "%43ON/%61ux.txt");
+    assert_eq!(names["what?.txt"], "what%3F.txt");
+    assert_eq!(names["trailing. "], "trailing.%20");
+    assert_eq!(names["README%~1.md"], "README%25~1.md");
+    assert_eq!(names["100%41.txt"], "100%2541.txt");
+    assert_eq!(names["a%2Fb.txt"], "a%252Fb.txt");
+}
+
+#[test]
+fn case_collisions() {
+    let names = host_names(PATHS.iter().cloned());
+
+    assert_eq!(names["readme.md"], "readme%~1.md");
+    assert_eq!(names["Dir"], "Dir");
+    assert_eq!(names["dir"], "dir%~1");
+    assert_eq!(names["dir/file.txt"], "dir%~1/file.txt");
+    assert_eq!(names["DIR/file.txt"], "DIR%~2/file.txt");
+}
+
+#[test]
+fn round_trip() {
+    let names = host_names(PATHS.iter().cloned());
+    assert_eq!(names.len(), PATHS.len() + 3);
+
+    for (path, name) in &names {
+        assert_eq!(unescape_entry_path(name), &path[..]);
+    }
+}
+
+#[test]
+fn literal_percent() {
+    for name in &["100%41.txt", "a%2Fb.txt", "%2e%2e", "file%20.txt", "%43ON.txt%~1", "README%~1%2E.md"] {
+        assert_eq!(unescape_entry_path(name), *name);
+    }
+}
@@ -0,0 +1,27 @@
+#![allow(clippy::redundant_field_names)]
+
+extern crate totalcmd_hrx;
+#[cfg(windows)]
+extern crate wcxhead;
+#[cfg(not(windows))]
+use totalcmd_hrx::wcxhead;
+extern crate libc;
+extern crate hrx;
+
+mod util;
+mod host_names;
+mod registry;
+mod mempack;
+mod handles;
+mod progress;
+mod guard;
+mod header;
+mod crc;
+mod write;
+mod host;
+mod settings;
+mod line_endings;
+mod sidecars;
+mod binary;
+mod extract;
+mod boundary;
@@ -0,0 +1,134 @@
+use totalcmd_hrx::{ProgressCallbacks, BinaryPolicy, LineEndings, MemPackState, ArchiveState, PackFlags, Config, Error, pack_archive};
+use self::super::util::prepare_dir;
+use std::path::Path;
+use hrx::HrxEntryData;
+use std::fs;
+
+
+static FILES: &[(&str, &[u8])] = &[("crlf.txt", b"a\r\nb\r\n"),
+                                   ("mixed.txt", b"a\r\nb\nc\r\n\r\nd\r"),
+                                   ("lf.txt", b"a\nb\n"),
+                                   ("binary.bin", b"\xFF\r\n\xFE\n")];
+
+
+#[test]
+fn kept() {
+    let dir = prepare_dir("line_endings", "kept");
+    let config = Config { binary_policy: BinaryPolicy::Base64, ..Config::default() };
+    pack(&dir, &config);
+
+    let archive = fs::read_to_string(dir.join("archive.hrx")).unwrap();
+    assert!(archive.contains("<===> crlf.txt\na\r\nb\r\n\n"));
+    assert!(!archive.contains("line-endings"));
+
+    assert_eq!(extract(&dir, &config), FILES.iter().map(|&(_, data)| data.to_vec()).collect::<Vec<_>>());
+}
+
+#[test]
+fn normalised() {
+    let dir = prepare_dir("line_endings", "normalised");
+    let config = Config {
+        binary_policy: BinaryPolicy::Base64,
+        line_endings: LineEndings::Lf,
+        ..Config::default()
+    };
+    pack(&dir, &config);
+
+    assert_eq!(fs::read_to_string(dir.join("archive.hrx")).unwrap(),
+               "<===>\nline-endings: crlf\n<===> crlf.txt\na\nb\n\n<===>\nline-endings: crlf 1,3-4\n<===> mixed.txt\na\nb\nc\n\nd\r\n<===> lf.txt\na\nb\n\n\
+                <===>\nencoding: base64\n<===> binary.bin\n/w0K/go=");
+
+    // Recorded line endings are restored whatever the policy
+    for &line_endings in &[LineEndings::Keep, LineEndings::Lf] {
+        assert_eq!(extract(&dir, &Config { line_endings: line_endings, ..config }),
+                   FILES.iter().map(|&(_, data)| data.to_vec()).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn crlf() {
+    let dir = prepare_dir("line_endings", "crlf");
+    let config = Config {
+        binary_policy: BinaryPolicy::Base64,
+        line_endings: LineEndings::Crlf,
+        ..Config::default()
+    };
+    pack(&dir, &config);
+
+    assert_eq!(extract(&dir, &config),
+               vec![b"a\r\nb\r\n".to_vec(), b"a\r\nb\nc\r\n\r\nd\r".to_vec(), b"a\r\nb\r\n".to_vec(), b"\xFF\r\n\xFE\n".to_vec()]);
+}
+
+#[test]
+fn foreign() {
+    let dir = prepare_dir("line_endings", "foreign");
+    fs::write(dir.join("archive.hrx"), "<===> lf.txt\na\nb\r\nc\n<===> dir/\n").unwrap();
+
+    assert_eq!(extract(&dir, &Config { line_endings: LineEndings::Crlf, ..Config::default() }), vec![b"a\r\nb\r\nc".to_vec()]);
+    assert_eq!(extract(&dir, &Config::default()), vec![b"a\nb\r\nc".to_vec()]);
+}
+
+#[test]
+fn invalid() {
+    let dir = prepare_dir("line_endings", "invalid");
+    for comment in &["line-endings: crlf 3", "line-endings: crlf 2-1", "line-endings: lf"] {
+        fs::write(dir.join("archive.hrx"), format!("<===>\n{}\n<===> a.txt\na\nb\n", comment)).unwrap();
+
+        let mut state = ArchiveState::open(dir.join("archive.hrx"), &Config::default()).unwrap();
+        assert!(state.next_entry().is_some());
+        assert_eq!(state.current_crc(), Err(Error::BadData));
+        assert_eq!(state.extract_current_entry(Some(dir.join("out")), Some("a.txt")), Err(Error::BadData));
+    }
+}
+
+#[test]
+fn mempack() {
+    let config = Config { line_endings: LineEndings::Lf, ..Config::default() };
+    for &(want_headers, expected) in &[(true, &b"<===>\nline-endings: crlf\n<===> file.txt\na\nb\r"[..]), (false, &b"a\r\nb\r"[..])] {
+        let mut state = MemPackState::new(want_headers, "file.txt", &config).unwrap();
+        assert_eq!(state.pack_to_mem(b"a\r\nb\r", &mut []), Ok((5, 0, false)));
+
+        let mut out = vec![0; 1024];
+        let (_, written, done) = state.pack_to_mem(&[], &mut out).unwrap();
+        assert!(done);
+        assert_eq!(&out[..written], expected);
+    }
+}
+
+
+/// Pack `FILES`, from `src/`, into `archive.hrx`
+fn pack(dir: &Path, config: &Config) {
+    fs::create_dir_all(dir.join("src")).unwrap();
+    for &(name, data) in FILES {
+        fs::write(dir.join("src").join(name), data).unwrap();
+    }
+
+    assert_eq!(pack_archive(dir.join("archive.hrx"),
+                            None::<&str>,
+                            dir.join("src"),
+                            FILES.iter().map(|&(name, _)| name),
+                            PackFlags::default(),
+                            config,
+                            &ProgressCallbacks::default()),
+               Ok(vec![]));
+}
+
+/// Test and extract every file in `archive.hrx` into a fresh `out/`, returning their contents
+fn extract(dir: &Path, config: &Config) -> Vec<Vec<u8>> {
+    let out = dir.join("out");
+    let _ = fs::remove_dir_all(&out);
+
+    let mut state = ArchiveState::open(dir.join("archive.hrx"), config).unwrap();
+    let mut extracted = vec![];
+    while let Some((_, entry, name)) = state.next_entry() {
+        if entry.data == HrxEntryData::Directory {
+            continue;
+        }
+
+        let name = name.to_string();
+        assert_eq!(state.test_current_entry(), Ok(()));
+        assert_eq!(state.extract_current_entry(Some(&out), Some(&name)), Ok(()));
+        extracted.push(fs::read(out.join(&name)).unwrap());
+    }
+    extracted
+}
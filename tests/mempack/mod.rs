@@ -0,0 +1,109 @@
+use hrx::{HrxEntryData, HrxArchive, HrxEntry};
+use totalcmd_hrx::util::HOST_PATH_SEPARATOR;
+use totalcmd_hrx::{LegacyEncoding, BinaryPolicy, LineEndings, MemPackState, Config, Error};
+use std::num::NonZeroUsize;
+
+
+static BODY: &str = "Line the first\n<===> not a boundary anymore\nLine the last\n";
+
+
+#[test]
+fn headers() {
+    for &(in_chunk, out_chunk) in &[(1, 1), (3, 7), (BODY.len(), 2), (BODY.len(), 1024)] {
+        assert_eq!(pack(true, &format!("dir{}file.txt", HOST_PATH_SEPARATOR), in_chunk, out_chunk), expected("dir/file.txt"));
+    }
+}
+
+#[test]
+fn no_headers() {
+    for &(in_chunk, out_chunk) in &[(1, 1), (5, 3), (BODY.len(), 1024)] {
+        assert_eq!(pack(false, "file.txt", in_chunk, out_chunk), BODY.as_bytes());
+    }
+}
+
+#[test]
+fn empty_window() {
+    let mut state = MemPackState::new(true, "file.txt", &Config::default()).unwrap();
+    assert_eq!(state.pack_to_mem(BODY.as_bytes(), &mut []), Ok((BODY.len(), 0, false)));
+    assert_eq!(state.pack_to_mem(&[], &mut []), Ok((0, 0, false)));
+
+    let mut out = vec![0; 1024];
+    let (_, written, done) = state.pack_to_mem(&[], &mut out).unwrap();
+    assert!(done);
+    assert_eq!(&out[..written], &expected("file.txt")[..]);
+}
+
+#[test]
+fn invalid_utf8() {
+    let mut state = MemPackState::new(false, "file.bin", &Config::default()).unwrap();
+    assert_eq!(state.pack_to_mem(&[0xC3], &mut []), Ok((1, 0, false)));
+    assert_eq!(state.pack_to_mem(&[], &mut [0; 16]), Err(Error::UnknownFormat));
+}
+
+#[test]
+fn binary() {
+    let pack = |binary_policy| {
+        let mut state = MemPackState::new(true, "file.bin", &Config { binary_policy: binary_policy, ..Config::default() }).unwrap();
+        assert_eq!(state.pack_to_mem(b"\x80\xFF\xE9\n", &mut []), Ok((4, 0, false)));
+
+        let mut out = vec![0; 1024];
+        state.pack_to_mem(&[], &mut out).map(|(_, written, _)| String::from_utf8(out[..written].to_vec()).unwrap())
+    };
+
+    assert_eq!(pack(BinaryPolicy::Skip), Err(Error::UnknownFormat));
+    assert_eq!(pack(BinaryPolicy::Base64), Ok("<===>\nencoding: base64\n<===> file.bin\ngP/pCg==".to_string()));
+    assert_eq!(pack(BinaryPolicy::Transcode(LegacyEncoding::Latin1)), Ok("<===> file.bin\n\u{80}ÿé\n".to_string()));
+}
+
+#[test]
+fn no_headers_unrecordable() {
+    let pack = |config: &Config, data: &[u8]| {
+        let mut state = MemPackState::new(false, "file", config).unwrap();
+        assert_eq!(state.pack_to_mem(data, &mut []), Ok((data.len(), 0, false)));
+
+        let mut out = vec![0; 1024];
+        state.pack_to_mem(&[], &mut out).map(|(_, written, _)| out[..written].to_vec())
+    };
+
+    assert_eq!(pack(&Config { binary_policy: BinaryPolicy::Base64, ..Config::default() }, b"\x80\xFF"), Err(Error::UnknownFormat));
+    assert_eq!(pack(&Config { binary_policy: BinaryPolicy::Transcode(LegacyEncoding::Latin1), ..Config::default() }, b"\xE9"),
+               Ok("é".as_bytes().to_vec()));
+    assert_eq!(pack(&Config { line_endings: LineEndings::Lf, ..Config::default() }, b"A\r\nB\n"), Ok(b"A\r\nB\n".to_vec()));
+}
+
+#[test]
+fn leading_space() {
+    assert_eq!(MemPackState::new(true, " file.txt", &Config::default()).err(), Some(Error::NotSupported));
+    assert!(MemPackState::new(true, "dir/ file.txt", &Config::default()).is_ok());
+}
+
+
+fn pack(want_headers: bool, name: &str, in_chunk: usize, out_chunk: usize) -> Vec<u8> {
+    let mut state = MemPackState::new(want_headers, name, &Config::default()).unwrap();
+    let mut out = vec![];
+    let mut out_buf = vec![0; out_chunk];
+
+    let mut input = BODY.as_bytes();
+    loop {
+        let (taken, written, done) = state.pack_to_mem(&input[..in_chunk.min(input.len())], &mut out_buf).unwrap();
+        input = &input[taken..];
+        out.extend_from_slice(&out_buf[..written]);
+
+        if done {
+            return out;
+        }
+    }
+}
+
+fn expected(name: &str) -> Vec<u8> {
+    let mut archive = HrxArchive::new(NonZeroUsize::new(4).unwrap());
+    archive.entries.insert(name.parse().unwrap(),
+                           HrxEntry {
+                               comment: None,
+                               data: HrxEntryData::File { body: Some(BODY.to_string()) },
+                           });
+
+    let mut out = vec![];
+    archive.serialise(&mut out).unwrap();
+    out
+}
@@ -0,0 +1,224 @@
+use totalcmd_hrx::util::HOST_PATH_SEPARATOR;
+use self::super::util;
+use totalcmd_hrx::{ProcessDataProc, ProgressCallbacks, ArchiveState, Config, SetProcessDataProc, CloseArchive, OpenArchive, ProcessFile, DeleteFiles,
+                   ReadHeader, PackFiles, modify_archive};
+use wcxhead::{self, tOpenArchiveData, tHeaderData};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Barrier, Mutex, Arc};
+use std::path::{PathBuf, Path};
+use std::ffi::{CString, CStr};
+use std::{iter, mem, ptr};
+use libc::{c_char, c_int};
+use std::fs;
+use std::thread;
+
+
+const JOBS: usize = 4;
+
+static PACKED: [AtomicUsize; JOBS] = [AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0)];
+static UNPACKED: [AtomicUsize; JOBS] = [AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0)];
+
+static DELETE_REPORTS: Mutex<Vec<(String, c_int)>> = Mutex::new(Vec::new());
+static CHUNK_REPORTS: Mutex<Vec<(String, c_int)>> = Mutex::new(Vec::new());
+
+macro_rules! callbacks {
+    ($counters:ident: $($name:ident => $idx:expr),*) => {
+        $(
+            extern "system" fn $name(_: *mut c_char, size: c_int) -> c_int {
+                // Negative sizes are percentages
+                if size > 0 {
+                    $counters[$idx].fetch_add(size as usize, Ordering::SeqCst);
+                }
+                1
+            }
+        )*
+    }
+}
+
+macro_rules! recorders {
+    ($($name:ident => $reports:ident),*) => {
+        $(
+            extern "system" fn $name(name: *mut c_char, size: c_int) -> c_int {
+                let name = unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned();
+                $reports.lock().unwrap().push((name, size));
+                1
+            }
+        )*
+    }
+}
+
+callbacks!(PACKED: packed_0 => 0, packed_1 => 1, packed_2 => 2, packed_3 => 3);
+callbacks!(UNPACKED: unpacked_0 => 0, unpacked_1 => 1, unpacked_2 => 2, unpacked_3 => 3);
+recorders!(delete_reports => DELETE_REPORTS, chunk_reports => CHUNK_REPORTS);
+
+static PACKED_CALLBACKS: [ProcessDataProc; JOBS] = [packed_0, packed_1, packed_2, packed_3];
+static UNPACKED_CALLBACKS: [ProcessDataProc; JOBS] = [unpacked_0, unpacked_1, unpacked_2, unpacked_3];
+
+
+#[test]
+fn pack() {
+    let barrier = Arc::new(Barrier::new(JOBS));
+    let jobs = (0..JOBS)
+        .map(|job| {
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                let dir = prepare_dir("pack", job);
+                let files = (0..10).map(|i| format!("file{}.txt", i)).collect::<Vec<_>>();
+                for (i, file) in files.iter().enumerate() {
+                    fs::write(dir.join(file), "A".repeat((job + 1) * 100 + i)).unwrap();
+                }
+
+                let mut packed_file = c_string(&dir.join("archive.hrx"));
+                let mut src_path = c_string(&dir);
+                let mut add_list = files.iter().flat_map(|f| f.bytes().chain(iter::once(0))).chain(iter::once(0)).collect::<Vec<_>>();
+
+                // INVALID_HANDLE_VALUE; every thread sets its callback before any of them packs
+                SetProcessDataProc(usize::MAX as _, PACKED_CALLBACKS[job]);
+                barrier.wait();
+                assert_eq!(unsafe {
+                               PackFiles(packed_file.as_mut_ptr(),
+                                         ptr::null_mut(),
+                                         src_path.as_mut_ptr(),
+                                         add_list.as_mut_ptr() as *mut c_char,
+                                         0)
+                           },
+                           0);
+
+                (0..10).map(|i| (job + 1) * 100 + i).sum::<usize>()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for (job, handle) in jobs.into_iter().enumerate() {
+        let expected = handle.join().unwrap();
+        assert_eq!(PACKED[job].load(Ordering::SeqCst), expected);
+    }
+}
+
+#[test]
+fn unpack() {
+    let barrier = Arc::new(Barrier::new(JOBS));
+    let jobs = (0..JOBS)
+        .map(|job| {
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                let dir = prepare_dir("unpack", job);
+                let archive = (0..10).map(|i| format!("<===> file{}.txt\n{}\n", i, "A".repeat((job + 1) * 100 + i))).collect::<String>();
+                fs::write(dir.join("archive.hrx"), archive).unwrap();
+
+                let mut arc_name = c_string(&dir.join("archive.hrx"));
+                let mut data = tOpenArchiveData {
+                    ArcName: arc_name.as_mut_ptr(),
+                    OpenMode: wcxhead::PK_OM_EXTRACT,
+                    OpenResult: 0,
+                    CmtBuf: ptr::null_mut(),
+                    CmtBufSize: 0,
+                    CmtSize: 0,
+                    CmtState: 0,
+                };
+                let handle = unsafe { OpenArchive(&mut data) };
+                assert!(!handle.is_null());
+
+                // Every archive is open and has its callback set before any of them is extracted
+                SetProcessDataProc(handle, UNPACKED_CALLBACKS[job]);
+                barrier.wait();
+
+                let out_dir = dir.join("out");
+                let mut header: tHeaderData = unsafe { mem::zeroed() };
+                while unsafe { ReadHeader(handle, &mut header) } == 0 {
+                    let name = unsafe { CStr::from_ptr(header.FileName.as_ptr()) }.to_string_lossy().into_owned();
+                    let mut dest_name = c_string(&out_dir.join(name));
+                    assert_eq!(unsafe { ProcessFile(handle, wcxhead::PK_EXTRACT, ptr::null_mut(), dest_name.as_mut_ptr()) }, 0);
+                }
+                assert_eq!(CloseArchive(handle), 0);
+
+                // The newline before a boundary belongs to it, so only the last body ends in one
+                (0..10).map(|i| (job + 1) * 100 + i).sum::<usize>() + 1
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for (job, handle) in jobs.into_iter().enumerate() {
+        let expected = handle.join().unwrap();
+        assert_eq!(UNPACKED[job].load(Ordering::SeqCst), expected);
+    }
+}
+
+#[test]
+fn delete_percentages() {
+    let dir = prepare_dir("delete_percentages", 0);
+    fs::write(dir.join("archive.hrx"), "<===> a?.txt\nA\n<===> dir/\n<===> dir/b.txt\nB\n<===> c.txt\nC\n").unwrap();
+
+    let callbacks = ProgressCallbacks {
+        ansi: Some(delete_reports),
+        wide: None,
+    };
+    let delete_list = ["a%3F.txt".to_string(), format!("dir{}*.*", HOST_PATH_SEPARATOR)];
+    assert_eq!(modify_archive(dir.join("archive.hrx"), delete_list.iter(), &Config::default(), &callbacks), Ok(()));
+
+    let archive = dir.join("archive.hrx").to_string_lossy().into_owned();
+    assert_eq!(*DELETE_REPORTS.lock().unwrap(),
+               vec![("a%3F.txt".to_string(), -100),
+                    (archive.clone(), -1033),
+                    ("dir".to_string(), -100),
+                    (format!("dir{}b.txt", HOST_PATH_SEPARATOR), -100),
+                    (archive.clone(), -1066),
+                    (archive, -1100)]);
+}
+
+#[test]
+fn chunked_extraction() {
+    let dir = prepare_dir("chunked_extraction", 0);
+    let body_len = ProgressCallbacks::CHUNK_SIZE * 2 + 100;
+    fs::write(dir.join("archive.hrx"), format!("<===> big.bin\n{}\n", "A".repeat(body_len))).unwrap();
+
+    let mut state = ArchiveState::open(dir.join("archive.hrx"), &Config::default()).unwrap();
+    state.progress = ProgressCallbacks {
+        ansi: Some(chunk_reports),
+        wide: None,
+    };
+    assert!(state.next_entry().is_some());
+    assert_eq!(state.extract_current_entry(None::<&str>, Some(dir.join("big.bin").to_string_lossy().into_owned())), Ok(()));
+
+    let name = dir.join("big.bin").to_string_lossy().into_owned();
+    assert_eq!(*CHUNK_REPORTS.lock().unwrap(),
+               vec![(name.clone(), ProgressCallbacks::CHUNK_SIZE as c_int),
+                    (name.clone(), ProgressCallbacks::CHUNK_SIZE as c_int),
+                    (name, 101)]);
+}
+
+#[test]
+fn thread_scoped() {
+    ProgressCallbacks {
+            ansi: Some(packed_0),
+            wide: None,
+        }
+        .set_for_thread();
+    assert!(ProgressCallbacks::for_thread().ansi.is_some());
+
+    thread::spawn(|| assert!(ProgressCallbacks::for_thread().ansi.is_none())).join().unwrap();
+}
+
+#[test]
+fn cleared_after_operation() {
+    let dir = prepare_dir("cleared_after_operation", 0);
+    fs::write(dir.join("archive.hrx"), "<===> a.txt\nA\n").unwrap();
+
+    // INVALID_HANDLE_VALUE
+    SetProcessDataProc(usize::MAX as _, packed_0);
+    let mut archive = c_string(&dir.join("archive.hrx"));
+    let mut delete_list = b"missing.txt\0\0".to_vec();
+    assert_ne!(unsafe { DeleteFiles(archive.as_mut_ptr(), delete_list.as_mut_ptr() as *mut c_char) }, 0);
+
+    assert!(ProgressCallbacks::for_thread().ansi.is_none());
+    assert!(ArchiveState::open(dir.join("archive.hrx"), &Config::default()).unwrap().progress.ansi.is_none());
+}
+
+
+fn prepare_dir(test_name: &str, job: usize) -> PathBuf {
+    util::prepare_dir("progress", &format!("{}-{}", test_name, job))
+}
+
+fn c_string(path: &Path) -> Vec<c_char> {
+    CString::new(path.to_string_lossy().into_owned()).unwrap().into_bytes_with_nul().into_iter().map(|b| b as c_char).collect()
+}
@@ -0,0 +1,51 @@
+use totalcmd_hrx::HandleRegistry;
+
+
+#[test]
+fn double_remove() {
+    let registry = HandleRegistry::new();
+    let handle = registry.insert(1);
+
+    assert!(registry.remove(handle));
+    assert!(!registry.remove(handle));
+}
+
+#[test]
+fn use_after_remove() {
+    let registry = HandleRegistry::new();
+    let handle = registry.insert(1);
+    assert_eq!(registry.with(handle, |s| *s += 1), Some(()));
+    assert_eq!(registry.with(handle, |s| *s), Some(2));
+
+    assert!(registry.remove(handle));
+    assert_eq!(registry.with(handle, |s| *s), None);
+}
+
+#[test]
+fn never_reused() {
+    let registry = HandleRegistry::new();
+    let first = registry.insert(1);
+    assert!(registry.remove(first));
+
+    let second = registry.insert(2);
+    assert_ne!(first, second);
+    assert_eq!(registry.with(first, |s| *s), None);
+    assert_eq!(registry.with(second, |s| *s), Some(2));
+}
+
+#[test]
+fn foreign() {
+    let registry = HandleRegistry::new();
+    let other = HandleRegistry::new();
+    registry.insert(1);
+    let other_handle = other.insert("other");
+
+    assert_eq!(registry.with(other_handle, |s| *s), None);
+    assert!(!registry.remove(other_handle));
+    assert_eq!(other.with(other_handle, |s| *s), Some("other"));
+
+    for &made_up in &[0, 0xDEADBEEF, usize::MAX] {
+        assert_eq!(registry.with(made_up, |s| *s), None);
+        assert!(!registry.remove(made_up));
+    }
+}
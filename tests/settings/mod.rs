@@ -0,0 +1,234 @@
+use totalcmd_hrx::{ProgressCallbacks, LegacyEncoding, BinaryPolicy, SettingKind, LineEndings, PackFlags, SortOrder, Settings, Config, pack_archive};
+use std::num::NonZeroUsize;
+use self::super::util::prepare_dir;
+use std::path::PathBuf;
+use std::fs;
+
+
+static ALL_KEYS: &str = "\
+[totalcmd-hrx]
+BoundaryLength=5
+ShrinkBoundary=1
+BinaryPolicy=windows-1252
+Backup=1
+TimestampsInComments=1
+CommentSidecars=1
+SortOrder=path
+LineEndings=crlf
+PanicLog=totalcmd-hrx.log
+";
+
+
+#[test]
+fn defaults() {
+    assert_eq!(Settings::parse(""), Settings::default());
+    assert_eq!(Settings::parse("[other]\nBoundaryLength=5\n"), Settings::default());
+}
+
+#[test]
+fn all_keys() {
+    assert_eq!(Settings::parse(ALL_KEYS), all_keys_settings());
+    assert_eq!(Settings::parse(&ALL_KEYS.to_uppercase()),
+               Settings { panic_log: Some(PathBuf::from("TOTALCMD-HRX.LOG")), ..all_keys_settings() });
+}
+
+#[test]
+fn aliases() {
+    let settings = Settings::parse("[totalcmd-hrx]\nShrinkBoundary=yes\nBackup=TRUE\nTimestampsInComments=on\nCommentSidecars=no\n");
+    assert_eq!((settings.config.shrink_boundary, settings.config.backup, settings.config.timestamps_in_comments, settings.config.comment_sidecars),
+               (true, true, true, false));
+}
+
+#[test]
+fn invalid() {
+    assert_eq!(Settings::parse("[totalcmd-hrx]\nBoundaryLength=0\nBackup=maybe\nBinaryPolicy=utf-7\nSortOrder\n; SortOrder=path\n"),
+               Settings::default());
+}
+
+#[test]
+fn round_trip() {
+    let settings = all_keys_settings();
+    assert_eq!(settings.write_into(""), if cfg!(windows) { ALL_KEYS.replace('\n', "\r\n") } else { ALL_KEYS.to_string() });
+    assert_eq!(Settings::parse(&settings.write_into("")), settings);
+}
+
+#[test]
+fn rest_kept() {
+    let ini = "[Other]\r\nBoundaryLength=7\r\n\r\n[TotalCmd-HRX]\r\n; Comment\r\nbackup=1\r\nUnknown=value\r\nBackup=0\r\n\r\n[Another]\r\nA=B\r\n";
+    let settings = Settings { config: Config { sort_order: SortOrder::Path, ..Config::default() }, ..Settings::default() };
+
+    assert_eq!(settings.write_into(ini),
+               "[Other]\r\nBoundaryLength=7\r\n\r\n[TotalCmd-HRX]\r\n; Comment\r\nBackup=0\r\nUnknown=value\r\nBoundaryLength=3\r\nShrinkBoundary=0\r\n\
+                BinaryPolicy=skip\r\nTimestampsInComments=0\r\nCommentSidecars=0\r\nSortOrder=path\r\nLineEndings=keep\r\nPanicLog=\r\n\r\n\
+                [Another]\r\nA=B\r\n");
+    assert_eq!(Settings::parse(&settings.write_into(ini)), settings);
+}
+
+#[test]
+fn line_endings_kept() {
+    let mixed = ALL_KEYS.replacen('\n', "\r\n", 3);
+    assert_eq!(all_keys_settings().write_into(&mixed), mixed);
+    assert_eq!(all_keys_settings().write_into(ALL_KEYS.trim_end()), ALL_KEYS.trim_end());
+
+    let settings = Settings { config: Config { backup: true, ..Config::default() }, ..Settings::default() };
+    assert_eq!(settings.write_into("[Other]\nA=B\r\n[totalcmd-hrx]\nBackup=0"),
+               "[Other]\nA=B\r\n[totalcmd-hrx]\nBackup=1\r\nBoundaryLength=3\r\nShrinkBoundary=0\r\nBinaryPolicy=skip\r\nTimestampsInComments=0\r\n\
+                CommentSidecars=0\r\nSortOrder=keep\r\nLineEndings=keep\r\nPanicLog=\r\n");
+}
+
+#[test]
+fn appended() {
+    assert!(Settings::default().write_into("[Other]\nA=B").starts_with("[Other]\nA=B\n\n[totalcmd-hrx]\nBoundaryLength=3\n"));
+    assert!(Settings::default().write_into("[Other]\nA=B\n\n").starts_with("[Other]\nA=B\n\n[totalcmd-hrx]\nBoundaryLength=3\n"));
+}
+
+#[test]
+fn load_or_init() {
+    let dir = prepare_dir("settings", "load_or_init");
+
+    let ini = dir.join("missing.ini");
+    assert_eq!(Settings::load_or_init(&ini), Settings::default());
+    assert_eq!(Settings::load(&ini), Ok(Settings::default()));
+
+    let ini = dir.join("other.ini");
+    fs::write(&ini, "[Other]\nA=B\n").unwrap();
+    assert_eq!(Settings::load_or_init(&ini), Settings::default());
+    assert!(fs::read_to_string(&ini).unwrap().starts_with("[Other]\nA=B\n\n[totalcmd-hrx]\n"));
+
+    let ini = dir.join("set.ini");
+    fs::write(&ini, "[totalcmd-hrx]\nSortOrder=path\n").unwrap();
+    assert_eq!(Settings::load_or_init(&ini).config.sort_order, SortOrder::Path);
+    assert_eq!(fs::read_to_string(&ini).unwrap(), "[totalcmd-hrx]\nSortOrder=path\n");
+}
+
+#[test]
+fn utf16_ini() {
+    let dir = prepare_dir("settings", "utf16");
+    let ini = dir.join("utf16.ini");
+    let original = "[totalcmd-hrx]\r\nSortOrder=path\r\nPanicLog=żółw.log\r\n";
+    fs::write(&ini, utf16(original)).unwrap();
+
+    let mut settings = Settings::load(&ini).unwrap();
+    assert_eq!(settings.config.sort_order, SortOrder::Path);
+    assert_eq!(settings.panic_log, Some(PathBuf::from("żółw.log")));
+
+    settings.config.backup = true;
+    assert_eq!(settings.save(&ini), Ok(()));
+    assert_eq!(fs::read(&ini).unwrap(), utf16(&settings.write_into(original)));
+    assert_eq!(Settings::load(&ini), Ok(settings));
+}
+
+#[test]
+fn ansi_ini() {
+    let dir = prepare_dir("settings", "ansi");
+    let ini = dir.join("ansi.ini");
+    fs::write(&ini, b"[Other]\r\nName=Gr\xFC\xDFe\r\n").unwrap();
+    assert_eq!(Settings::load_or_init(&ini), Settings::default());
+    assert!(fs::read(&ini).unwrap().starts_with(b"[Other]\r\nName=Gr\xFC\xDFe\r\n\r\n[totalcmd-hrx]\r\n"));
+
+    let original: &[u8] = b"[Other]\r\nName=Gr\xFC\xDFe\r\n\r\n[totalcmd-hrx]\r\nPanicLog=\xC5\xBC\xC3\xB3\xC5\x82w.log\r\n\r\n[\xC9t\xE9]\r\nA=\xE9\r\n";
+    fs::write(&ini, original).unwrap();
+
+    let mut settings = Settings::load(&ini).unwrap();
+    assert_eq!(settings.panic_log, Some(PathBuf::from("żółw.log")));
+
+    settings.config.backup = true;
+    assert_eq!(settings.save(&ini), Ok(()));
+    let saved = fs::read(&ini).unwrap();
+    assert!(saved.starts_with(b"[Other]\r\nName=Gr\xFC\xDFe\r\n\r\n[totalcmd-hrx]\r\nPanicLog=\xC5\xBC\xC3\xB3\xC5\x82w.log\r\nBoundaryLength=3\r\n"));
+    assert!(saved.ends_with(b"\r\n\r\n[\xC9t\xE9]\r\nA=\xE9\r\n"));
+    assert_eq!(Settings::load(&ini), Ok(settings));
+}
+
+#[test]
+fn fields() {
+    let settings = all_keys_settings();
+    for field in &Settings::FIELDS {
+        let value = settings.value(field.key).unwrap();
+        assert_eq!(ALL_KEYS.lines().filter(|l| **l == format!("{}={}", field.key, value)).count(), 1, "{}", field.key);
+
+        let mut edited = Settings::default();
+        assert!(edited.set_value(field.key, &value), "{}", field.key);
+        assert_eq!(edited.value(field.key), Some(value), "{}", field.key);
+
+        match field.kind {
+            SettingKind::Flag => assert!(edited.set_value(field.key, "1") && edited.set_value(field.key, "0") && !edited.set_value(field.key, "")),
+            SettingKind::Number => assert!(edited.set_value(field.key, "7") && !edited.set_value(field.key, "0") && !edited.set_value(field.key, "seven")),
+            SettingKind::Choice(choices) => {
+                assert!(choices.iter().all(|c| edited.set_value(field.key, c) && edited.value(field.key).as_ref().map(|v| &v[..]) == Some(*c)));
+                assert!(!edited.set_value(field.key, ""));
+            }
+            SettingKind::Text => assert!(edited.set_value(field.key, "") && edited.set_value(field.key, "any thing")),
+        }
+    }
+
+    assert_eq!(settings.value("Unknown"), None);
+    assert!(!Settings::default().set_value("Unknown", "1"));
+}
+
+#[test]
+fn edit() {
+    let dir = prepare_dir("settings", "edit");
+    let ini = dir.join("edit.ini");
+    let original = "[Other]\r\nA=B\r\n\r\n[totalcmd-hrx]\r\n; Comment\r\nSortOrder=path\r\n";
+    fs::write(&ini, original).unwrap();
+
+    assert_eq!(Settings::edit(&ini, |s| {
+                   assert_eq!(s.config.sort_order, SortOrder::Path);
+                   s.set_value("Backup", "1")
+               }),
+               Ok(Some(Settings { config: Config { backup: true, sort_order: SortOrder::Path, ..Config::default() }, ..Settings::default() })));
+    let saved = fs::read_to_string(&ini).unwrap();
+    assert!(saved.starts_with("[Other]\r\nA=B\r\n\r\n[totalcmd-hrx]\r\n; Comment\r\n"));
+    assert!(saved.contains("\r\nBackup=1\r\n"));
+
+    assert_eq!(Settings::edit(&ini, |s| !s.set_value("Backup", "0")), Ok(None));
+    assert_eq!(fs::read_to_string(&ini).unwrap(), saved);
+
+    let missing = dir.join("missing.ini");
+    assert_eq!(Settings::edit(&missing, |_| true), Ok(Some(Settings::default())));
+    assert_eq!(Settings::load(&missing), Ok(Settings::default()));
+}
+
+#[test]
+fn sort_order() {
+    let dir = prepare_dir("settings", "sort_order");
+    fs::write(dir.join("archive.hrx"), "<===> z.txt\nZ\n<===> dir-a.txt\nA").unwrap();
+    fs::create_dir_all(dir.join("dir")).unwrap();
+    for file in &["b.txt", "dir/c.txt"] {
+        fs::write(dir.join(file), "content").unwrap();
+    }
+
+    let config = Config { sort_order: SortOrder::Path, ..Config::default() };
+    assert_eq!(pack_archive(dir.join("archive.hrx"),
+                            None::<&str>,
+                            &dir,
+                            ["b.txt", "dir/c.txt"].iter(),
+                            PackFlags { save_paths: true, ..PackFlags::default() },
+                            &config,
+                            &ProgressCallbacks::default()),
+               Ok(vec![]));
+    assert_eq!(fs::read_to_string(dir.join("archive.hrx")).unwrap(),
+               "<===> b.txt\ncontent\n<===> dir/c.txt\ncontent\n<===> dir-a.txt\nA\n<===> z.txt\nZ");
+}
+
+
+fn all_keys_settings() -> Settings {
+    Settings {
+        config: Config {
+            boundary_length: NonZeroUsize::new(5).unwrap(),
+            shrink_boundary: true,
+            binary_policy: BinaryPolicy::Transcode(LegacyEncoding::Windows1252),
+            backup: true,
+            timestamps_in_comments: true,
+            comment_sidecars: true,
+            sort_order: SortOrder::Path,
+            line_endings: LineEndings::Crlf,
+        },
+        panic_log: Some(PathBuf::from("totalcmd-hrx.log")),
+    }
+}
+
+fn utf16(s: &str) -> Vec<u8> {
+    b"\xFF\xFE".iter().cloned().chain(s.encode_utf16().flat_map(u16::to_le_bytes)).collect()
+}
@@ -0,0 +1,99 @@
+use totalcmd_hrx::{ProgressCallbacks, ArchiveState, PackFlags, Config, Error, modify_archive, pack_archive};
+use self::super::util::prepare_dir;
+use std::path::Path;
+use std::fs;
+
+
+static ARCHIVE: &str = "<===>\nencoding: base64\nNote\n<===> a.bin\nQQ==\n<===> b.txt\nB\n";
+
+
+#[test]
+fn listed() {
+    let dir = prepare_dir("sidecars", "listed");
+    fs::write(dir.join("archive.hrx"), ARCHIVE).unwrap();
+
+    assert_eq!(names(&dir, &sidecars()), vec!["a.bin", "a.bin.comment", "b.txt"]);
+    assert_eq!(names(&dir, &Config::default()), vec!["a.bin", "b.txt"]);
+
+    let mut state = ArchiveState::open(dir.join("archive.hrx"), &sidecars()).unwrap();
+    state.next_entry();
+    state.next_entry();
+    assert_eq!(state.test_current_entry(), Ok(()));
+    assert_eq!(state.extract_current_entry(Some(dir.join("out")), Some("a.bin.comment")), Ok(()));
+    assert_eq!(fs::read_to_string(dir.join("out").join("a.bin.comment")).unwrap(), "Note");
+}
+
+#[test]
+fn packed() {
+    let dir = prepare_dir("sidecars", "packed");
+    fs::write(dir.join("archive.hrx"), ARCHIVE).unwrap();
+    fs::write(dir.join("a.bin.comment"), "New note").unwrap();
+    fs::write(dir.join("b.txt.comment"), "Also new").unwrap();
+
+    assert_eq!(pack(&dir, &["a.bin.comment", "b.txt.comment"], &sidecars()), Ok(vec![]));
+    assert_eq!(fs::read_to_string(dir.join("archive.hrx")).unwrap(),
+               "<===>\nNew note\nencoding: base64\n<===> a.bin\nQQ==\n<===>\nAlso new\n<===> b.txt\nB\n");
+}
+
+#[test]
+fn packed_without_entry() {
+    let dir = prepare_dir("sidecars", "packed_without_entry");
+    fs::write(dir.join("archive.hrx"), ARCHIVE).unwrap();
+    fs::write(dir.join("c.txt.comment"), "Orphan").unwrap();
+
+    assert_eq!(pack(&dir, &["c.txt.comment"], &sidecars()), Ok(vec![]));
+    assert!(fs::read_to_string(dir.join("archive.hrx")).unwrap().ends_with("<===> c.txt.comment\nOrphan"));
+}
+
+#[test]
+fn deleted() {
+    let dir = prepare_dir("sidecars", "deleted");
+    fs::write(dir.join("archive.hrx"), ARCHIVE).unwrap();
+
+    assert_eq!(delete(&dir, &["a.bin.comment"], &Config::default()), Err(Error::NoFiles));
+    assert_eq!(delete(&dir, &["a.bin.comment"], &sidecars()), Ok(()));
+    assert_eq!(fs::read_to_string(dir.join("archive.hrx")).unwrap(), "<===>\nencoding: base64\n<===> a.bin\nQQ==\n<===> b.txt\nB\n");
+    assert_eq!(names(&dir, &sidecars()), vec!["a.bin", "b.txt"]);
+}
+
+#[test]
+fn deleted_with_entry() {
+    let dir = prepare_dir("sidecars", "deleted_with_entry");
+    fs::write(dir.join("archive.hrx"), ARCHIVE).unwrap();
+
+    assert_eq!(delete(&dir, &["a.bin*"], &sidecars()), Ok(()));
+    assert_eq!(fs::read_to_string(dir.join("archive.hrx")).unwrap(), "<===> b.txt\nB\n");
+}
+
+#[test]
+fn real_entry() {
+    let dir = prepare_dir("sidecars", "real_entry");
+    fs::write(dir.join("archive.hrx"), "<===>\nNote\n<===> a.txt\nA\n<===> a.txt.comment\nReal\n<===> dir.comment/c.txt\nC\n<===> dir\n").unwrap();
+
+    assert_eq!(names(&dir, &sidecars()), vec!["a.txt", "a.txt.comment", "dir.comment", "dir.comment/c.txt", "dir"]);
+    assert_eq!(delete(&dir, &["a.txt.comment", "dir.comment"], &sidecars()), Ok(()));
+    assert_eq!(fs::read_to_string(dir.join("archive.hrx")).unwrap(), "<===>\nNote\n<===> a.txt\nA\n<===> dir\n");
+}
+
+
+fn sidecars() -> Config {
+    Config { comment_sidecars: true, ..Config::default() }
+}
+
+/// List `archive.hrx` as `/`-separated host names
+fn names(dir: &Path, config: &Config) -> Vec<String> {
+    let mut state = ArchiveState::open(dir.join("archive.hrx"), config).unwrap();
+    let mut names = vec![];
+    while let Some((_, _, name)) = state.next_entry() {
+        names.push(name.to_string());
+    }
+    names
+}
+
+fn pack(dir: &Path, files: &[&str], config: &Config) -> Result<Vec<String>, Error> {
+    pack_archive(dir.join("archive.hrx"), None::<&str>, dir, files.iter(), PackFlags::default(), config, &ProgressCallbacks::default())
+}
+
+fn delete(dir: &Path, names: &[&str], config: &Config) -> Result<(), Error> {
+    modify_archive(dir.join("archive.hrx"), names.iter(), config, &ProgressCallbacks::default())
+}
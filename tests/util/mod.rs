@@ -0,0 +1,23 @@
+use totalcmd_hrx::{ArchiveState, Config};
+use std::path::{PathBuf, Path};
+use std::{env, iter, fs};
+
+
+/// Get an empty scratch directory for the specified test of the specified module
+pub fn prepare_dir(module: &str, test_name: &str) -> PathBuf {
+    let dir = env::temp_dir().join("totalcmd-hrx-test").join(module).join(test_name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Write the specified archive into `archive.hrx` in the specified directory and open it
+pub fn open(dir: &Path, archive: &str) -> ArchiveState {
+    fs::write(dir.join("archive.hrx"), archive).unwrap();
+    ArchiveState::open(dir.join("archive.hrx"), &Config::default()).unwrap()
+}
+
+/// NUL-terminated UTF-16 of the specified string
+pub fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(iter::once(0)).collect()
+}
@@ -0,0 +1,134 @@
+use self::super::util;
+use totalcmd_hrx::{write_archive_with, write_archive, Error};
+use std::io::{Error as IoError, BufWriter, Write, Result as IoResult};
+use std::sync::{Barrier, Arc};
+use std::path::PathBuf;
+use hrx::HrxArchive;
+use std::thread;
+use std::fs;
+use std::fs::File;
+
+
+static ORIGINAL: &str = "<===> original.txt\nOriginal content\n";
+static MODIFIED: &str = "<===> modified.txt\nModified content\n";
+
+
+struct FailingWriter {
+    out: File,
+    left: usize,
+}
+
+impl Write for FailingWriter {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        if self.left == 0 {
+            return Err(IoError::other("simulated failure"));
+        }
+
+        let written = self.out.write(&buf[..buf.len().min(self.left)])?;
+        self.left -= written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.out.flush()
+    }
+}
+
+
+#[test]
+fn failure_keeps_original() {
+    let dir = prepare_dir("failure_keeps_original");
+    let packed_file = dir.join("archive.hrx");
+
+    for &left in &[0, 1, 10, MODIFIED.len() - 1] {
+        assert_eq!(write_archive_with(&MODIFIED.parse::<HrxArchive>().unwrap(),
+                                      &packed_file,
+                                      true,
+                                      |out| FailingWriter { out: out, left: left }),
+                   Err(Error::Write));
+
+        assert_eq!(fs::read_to_string(&packed_file).unwrap(), ORIGINAL);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    }
+}
+
+#[test]
+fn success_replaces() {
+    let dir = prepare_dir("success_replaces");
+    let packed_file = dir.join("archive.hrx");
+
+    assert_eq!(write_archive(&MODIFIED.parse::<HrxArchive>().unwrap(), &packed_file, false), Ok(()));
+
+    assert_eq!(fs::read_to_string(&packed_file).unwrap(), MODIFIED);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+}
+
+#[test]
+fn success_backs_up() {
+    let dir = prepare_dir("success_backs_up");
+    let packed_file = dir.join("archive.hrx");
+
+    assert_eq!(write_archive(&MODIFIED.parse::<HrxArchive>().unwrap(), &packed_file, true), Ok(()));
+
+    assert_eq!(fs::read_to_string(&packed_file).unwrap(), MODIFIED);
+    assert_eq!(fs::read_to_string(dir.join("archive.hrx.bak")).unwrap(), ORIGINAL);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+}
+
+#[test]
+fn success_keeps_permissions() {
+    let dir = prepare_dir("success_keeps_permissions");
+    let packed_file = dir.join("archive.hrx");
+
+    let original_permissions = fs::metadata(&packed_file).unwrap().permissions();
+    let mut permissions = original_permissions.clone();
+    permissions.set_readonly(true);
+    fs::set_permissions(&packed_file, permissions).unwrap();
+
+    let result = write_archive(&MODIFIED.parse::<HrxArchive>().unwrap(), &packed_file, false);
+    let readonly = fs::metadata(&packed_file).unwrap().permissions().readonly();
+
+    // Replacing read-only files fails on Windows, in which case the original needs to stay intact
+    if result.is_ok() {
+        assert_eq!(fs::read_to_string(&packed_file).unwrap(), MODIFIED);
+    } else {
+        assert_eq!(fs::read_to_string(&packed_file).unwrap(), ORIGINAL);
+    }
+    assert!(readonly);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+    fs::set_permissions(&packed_file, original_permissions).unwrap();
+}
+
+#[test]
+fn concurrent_writes() {
+    let dir = prepare_dir("concurrent_writes");
+    let packed_file = dir.join("archive.hrx");
+
+    // Every thread has its temporary file open before any of them writes
+    let barrier = Arc::new(Barrier::new(8));
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let (packed_file, barrier) = (packed_file.clone(), barrier.clone());
+            thread::spawn(move || {
+                write_archive_with(&MODIFIED.parse::<HrxArchive>().unwrap(), &packed_file, false, |out| {
+                    barrier.wait();
+                    BufWriter::new(out)
+                })
+            })
+        })
+        .collect();
+    for thread in threads {
+        assert_eq!(thread.join().unwrap(), Ok(()));
+    }
+
+    assert_eq!(fs::read_to_string(&packed_file).unwrap(), MODIFIED);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+}
+
+
+fn prepare_dir(test_name: &str) -> PathBuf {
+    let dir = util::prepare_dir("write", test_name);
+    fs::write(dir.join("archive.hrx"), ORIGINAL).unwrap();
+    dir
+}